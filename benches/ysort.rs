@@ -0,0 +1,96 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use bevy::{asset::AssetPlugin, prelude::*, transform::TransformPlugin};
+use bevy_learning::ysort::{YSort, YSortBands, YSortPivot, YSortPlugin};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const SPRITES: usize = 10_000;
+
+/// `sort` of the previous `YSortPlugin`, kept as the baseline: every layer sorted and every
+/// `Transform` written each frame. Only its order is reversed, to draw lower entities in front as
+/// the plugin does now.
+fn full_sort(
+    bands: Res<YSortBands>,
+    mut query: Query<(&mut Transform, &YSort, Option<&YSortPivot>)>,
+) {
+    let mut transformers: HashMap<i32, Vec<(f32, Mut<Transform>)>> = HashMap::new();
+    for (transform, ysort, pivot) in query.iter_mut() {
+        let y = transform.translation.y + pivot.map_or(0., |pivot| pivot.0) * transform.scale.y;
+        transformers
+            .entry(ysort.0)
+            .or_default()
+            .push((y, transform));
+    }
+
+    for (layer, v) in transformers.iter_mut() {
+        v.sort_unstable_by(|(a, _), (b, _)| match b.partial_cmp(a) {
+            None => Ordering::Equal,
+            Some(other) => other,
+        });
+
+        let band = bands.get(*layer);
+        let step = (band.end - band.start) / (v.len() + 1) as f32;
+        for (index, (_, transform)) in v.iter_mut().enumerate() {
+            transform.translation.z = band.start + step * (index + 1) as f32;
+        }
+    }
+}
+
+/// App with `SPRITES` sprites spread over 4 layers, and the first `moving` of them.
+fn app(incremental: bool, moving: usize) -> (App, Vec<Entity>) {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(AssetPlugin)
+        .add_plugin(TransformPlugin)
+        .add_asset::<Image>()
+        .add_asset::<TextureAtlas>();
+    if incremental {
+        app.add_plugin(YSortPlugin);
+    } else {
+        app.init_resource::<YSortBands>()
+            .add_system_to_stage(CoreStage::PostUpdate, full_sort);
+    }
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let entities: Vec<_> = (0..SPRITES)
+        .map(|index| {
+            let translation = Vec3::new(rng.gen_range(0.0..640.), rng.gen_range(0.0..360.), 0.);
+            app.world
+                .spawn()
+                .insert_bundle(TransformBundle::from(Transform::from_translation(
+                    translation,
+                )))
+                .insert_bundle((YSort(index as i32 % 4), YSortPivot(-8.)))
+                .id()
+        })
+        .collect();
+    app.update();
+
+    (app, entities.into_iter().take(moving).collect())
+}
+
+fn ysort(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ysort");
+    for moving in [0, SPRITES / 100, SPRITES] {
+        for (name, incremental) in [("full", false), ("incremental", true)] {
+            group.bench_with_input(BenchmarkId::new(name, moving), &moving, |b, &moving| {
+                let (mut app, moving) = app(incremental, moving);
+                let mut step = 1.;
+                b.iter(|| {
+                    step = -step;
+                    for entity in moving.iter() {
+                        if let Some(mut transform) = app.world.get_mut::<Transform>(*entity) {
+                            transform.translation.y += step;
+                        }
+                    }
+                    app.update();
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, ysort);
+criterion_main!(benches);
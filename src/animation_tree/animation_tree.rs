@@ -1,6 +1,19 @@
-use benimator::{Play, SpriteSheetAnimation, SpriteSheetAnimationState};
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use benimator::SpriteSheetAnimation;
 use bevy::prelude::*;
-use bevy::utils::HashMap;
+use bevy::utils::{HashMap, HashSet};
+use rand::Rng;
+use serde::Deserialize;
+
+use super::{
+    AnimationFinished, AnimationLayer, AnimationMarker, AnimationParameter, AnimationStarted,
+    AnimationSubMachine, AnimationTrack, AnimationTransition, AnimationTreeState, BlendSpace,
+    ParameterCondition, TransitionCondition,
+};
+
+/// Fastest playback of a node, faster skips most frames and overflows `Duration` in the end.
+pub(crate) const MAX_SPEED: f32 = 1000.;
 
 #[derive(Clone, Default)]
 pub struct Animation {
@@ -11,29 +24,167 @@ impl Animation {
     pub fn from(animation: Handle<SpriteSheetAnimation>) -> Self {
         Animation { handle: animation }
     }
+
+    pub fn handle(&self) -> &Handle<SpriteSheetAnimation> {
+        &self.handle
+    }
 }
 
 #[derive(Clone)]
 pub struct AnimationNode {
     animation: Animation,
     point: Vec2,
+    flip_x: bool,
+    durations: Vec<Duration>,
 }
 
 impl AnimationNode {
     pub fn from(animation: Animation, point: Vec2) -> Self {
-        AnimationNode { animation, point }
+        AnimationNode {
+            animation,
+            point,
+            flip_x: false,
+            durations: Vec::new(),
+        }
+    }
+
+    /// Hold each frame for its duration instead of the one of the animation, e.g. to hold a
+    /// wind-up frame longer. Frames past the end of `durations` keep their own.
+    pub fn durations(mut self, durations: Vec<Duration>) -> Self {
+        self.durations = durations;
+        self
+    }
+
+    /// Mirror the sprite, e.g. to play the right facing frames when going left.
+    pub fn flip_x(mut self) -> Self {
+        self.flip_x = true;
+        self
     }
 }
 
+/// How the animation of a node plays once it reached its last frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+pub enum AnimationMode {
+    #[default]
+    Repeat,
+    /// Stop on the last frame and send `AnimationFinished`.
+    Once,
+}
+
+/// Order the frames of a node animation play in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+pub enum AnimationPlayback {
+    #[default]
+    Forward,
+    /// From the last frame to the first.
+    Reverse,
+    /// Forward then back to the first frame, e.g. for breathing or wing flaps.
+    PingPong,
+}
+
+impl AnimationPlayback {
+    fn first_frame(self, len: usize) -> usize {
+        match self {
+            AnimationPlayback::Reverse => len.saturating_sub(1),
+            _ => 0,
+        }
+    }
+
+    /// Frame after `frame` and whether it plays backward, `None` once a non-looping animation
+    /// played its last frame.
+    fn advance(
+        self,
+        frame: usize,
+        backward: bool,
+        len: usize,
+        mode: AnimationMode,
+    ) -> Option<(usize, bool)> {
+        let repeat = mode == AnimationMode::Repeat;
+        match self {
+            AnimationPlayback::Forward if frame + 1 < len => Some((frame + 1, false)),
+            AnimationPlayback::Forward => repeat.then_some((0, false)),
+            AnimationPlayback::Reverse if frame > 0 => Some((frame - 1, true)),
+            AnimationPlayback::Reverse => repeat.then_some((len - 1, true)),
+            AnimationPlayback::PingPong if !backward && frame + 1 < len => Some((frame + 1, false)),
+            AnimationPlayback::PingPong if frame > 0 => Some((frame - 1, true)),
+            // Back on the first frame.
+            AnimationPlayback::PingPong => repeat.then_some((1.min(len - 1), false)),
+        }
+    }
+}
+
+/// What happens to the playing frame when the blend point picks another animation of the node.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+pub enum DirectionSwitch {
+    /// Start the new animation from its first frame.
+    #[default]
+    Restart,
+    /// Keep the frame index and the time spent in it.
+    KeepFrame,
+    /// Keep the elapsed part of the animation, for animations of different lengths.
+    KeepTime,
+}
+
 #[derive(Clone)]
 pub struct AnimationNodeMachine {
     points: Vec<AnimationNode>,
+    mode: AnimationMode,
+    playback: AnimationPlayback,
+    random_start: bool,
+    direction_switch: DirectionSwitch,
+    speed: f32,
     next: Option<String>,
+    markers: Vec<(usize, String)>,
+    transitions: Vec<AnimationTransition>,
+    /// Transitions of the enclosing sub-machines, allowed on top of the own ones.
+    inherited: Vec<AnimationTransition>,
+    tracks: Vec<AnimationTrack>,
+    blend: BlendSpace,
 }
 
 impl AnimationNodeMachine {
     pub fn from(points: Vec<AnimationNode>) -> Self {
-        AnimationNodeMachine { points, next: None }
+        AnimationNodeMachine {
+            points,
+            mode: AnimationMode::Repeat,
+            playback: AnimationPlayback::Forward,
+            random_start: false,
+            direction_switch: DirectionSwitch::Restart,
+            speed: 1.,
+            next: None,
+            markers: Vec::new(),
+            transitions: Vec::new(),
+            inherited: Vec::new(),
+            tracks: Vec::new(),
+            blend: BlendSpace::default(),
+        }
+    }
+
+    pub fn mode(mut self, mode: AnimationMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn playback(mut self, playback: AnimationPlayback) -> Self {
+        self.playback = playback;
+        self
+    }
+
+    /// Start the animation on a random frame, so identical entities do not play in lockstep.
+    pub fn random_start(mut self) -> Self {
+        self.random_start = true;
+        self
+    }
+
+    pub fn direction_switch(mut self, direction_switch: DirectionSwitch) -> Self {
+        self.direction_switch = direction_switch;
+        self
+    }
+
+    /// Scale the playback of this node, on top of the speed of the tree.
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
     }
 
     pub fn next(mut self, next: String) -> Self {
@@ -41,28 +192,174 @@ impl AnimationNodeMachine {
         self
     }
 
-    pub(crate) fn play(&self, point: Vec2) -> Animation {
-        let mut next_animation = Animation::default();
-        let mut distance: f32 = f32::MAX;
-        for p in self.points.iter() {
-            let dis = point.distance(p.point);
-            if dis < distance {
-                distance = dis;
-                next_animation = p.animation.clone();
-            }
+    /// Emit an `AnimationMarker` named `marker` when the animation reaches `frame`.
+    pub fn marker(mut self, frame: usize, marker: String) -> Self {
+        self.markers.push((frame, marker));
+        self
+    }
+
+    /// Only allow leaving this node through `transition`.
+    ///
+    /// A node without any transition can travel to every other node at any time.
+    pub fn transition(mut self, transition: AnimationTransition) -> Self {
+        self.transitions.push(transition);
+        self
+    }
+
+    /// Drive a component field from the frames of this node.
+    pub fn track(mut self, mut track: AnimationTrack) -> Self {
+        track.keyframes.sort_by_key(|(frame, _)| *frame);
+        self.tracks.push(track);
+        self
+    }
+
+    pub fn tracks(&self) -> &[AnimationTrack] {
+        &self.tracks
+    }
+
+    pub fn transitions(&self) -> &[AnimationTransition] {
+        &self.transitions
+    }
+
+    /// Pick the animation of the points with `blend` instead of the closest declared first.
+    pub fn blend(mut self, blend: BlendSpace) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Copy of this node with its targets named by `resolve`, inside a sub-machine left through
+    /// `inherited`.
+    pub(crate) fn scoped(
+        &self,
+        resolve: impl Fn(&str) -> String,
+        inherited: &[AnimationTransition],
+    ) -> Self {
+        let mut node = self.clone();
+        node.next = node.next.map(|next| resolve(&next));
+        for transition in node.transitions.iter_mut() {
+            transition.to = resolve(&transition.to);
         }
+        node.inherited = inherited.to_vec();
+        node
+    }
+
+    /// Leave to `next` once done, unless the node already has its own next node.
+    pub(crate) fn exit_to(mut self, next: Option<String>) -> Self {
+        if self.next.is_none() {
+            self.next = next;
+        }
+        self
+    }
+
+    fn flip_x(&self, direction: Option<usize>) -> bool {
+        direction
+            .and_then(|direction| self.points.get(direction))
+            .map_or(false, |p| p.flip_x)
+    }
 
-        next_animation
+    /// Duration of `frame` of `animation` played by the point `direction`.
+    fn frame_duration(
+        &self,
+        direction: Option<usize>,
+        animation: &SpriteSheetAnimation,
+        frame: usize,
+    ) -> Duration {
+        direction
+            .and_then(|direction| self.points.get(direction))
+            .and_then(|p| p.durations.get(frame).copied())
+            .or_else(|| animation.frames.get(frame).map(|f| f.duration))
+            .unwrap_or_default()
+    }
+
+    fn durations(
+        &self,
+        direction: Option<usize>,
+        animation: &SpriteSheetAnimation,
+    ) -> Vec<Duration> {
+        (0..animation.frames.len())
+            .map(|frame| self.frame_duration(direction, animation, frame))
+            .collect()
+    }
+
+    /// Nodes reachable from this node, through a transition or the `next` node.
+    fn neighbors<'a>(
+        &'a self,
+        nodes: &'a HashMap<String, AnimationNodeMachine>,
+    ) -> Vec<&'a String> {
+        let mut neighbors: Vec<&String> = if self.transitions.is_empty() {
+            nodes.keys().collect()
+        } else {
+            self.transitions
+                .iter()
+                .map(|transition| &transition.to)
+                .collect()
+        };
+        neighbors.extend(self.inherited.iter().map(|transition| &transition.to));
+        neighbors.extend(self.next.iter());
+        // Keep the path stable when several paths have the same length.
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        neighbors
+    }
+
+    fn condition(&self, to_node: &str) -> Option<TransitionCondition> {
+        let condition = self
+            .transitions
+            .iter()
+            .chain(self.inherited.iter())
+            .find(|transition| transition.to == to_node)
+            .map(|transition| transition.condition);
+        if condition.is_none() && self.transitions.is_empty() {
+            return Some(TransitionCondition::Immediate);
+        }
+        condition
+    }
+
+    /// Pick the animation for `point`, `current` being the index of the point already playing.
+    pub(crate) fn play(&self, point: Vec2, current: Option<usize>) -> (usize, Animation) {
+        let points: Vec<Vec2> = self.points.iter().map(|p| p.point).collect();
+        let index = self.blend.pick(&points, point, current).unwrap_or(0);
+        let animation = self
+            .points
+            .get(index)
+            .map(|p| p.animation.clone())
+            .unwrap_or_default();
+
+        (index, animation)
     }
 }
 
-#[derive(Component, Clone)]
+#[derive(Component, Clone, Reflect)]
+#[reflect(Component)]
 pub struct AnimationTree {
     current: String,
     next: String,
-    pub finished: String,
     point: Vec2,
-    pub nodes: HashMap<String, AnimationNodeMachine>,
+    direction: Option<usize>,
+    frame: usize,
+    elapsed: Duration,
+    shown: Option<usize>,
+    /// The current node was just entered, a random start still applies.
+    entered: bool,
+    /// The frames play from the last to the first, in reverse or on the way back of a ping-pong.
+    backward: bool,
+    /// Flip last written to the sprite, the flip set by other systems stays until it changes.
+    flipped: Option<bool>,
+    stopped: bool,
+    wait_end: bool,
+    pending: Vec<String>,
+    parameters: HashMap<String, AnimationParameter>,
+    blend_parameter: Option<String>,
+    /// Playback speed of the whole tree, e.g. `0.5` while slowed down.
+    pub speed: f32,
+    /// Shared by every instance of the same tree, listed by the animation tree window.
+    #[reflect(ignore)]
+    nodes: Arc<HashMap<String, AnimationNodeMachine>>,
+    /// Entry node of every sub-machine, by full name of the sub-machine.
+    #[reflect(ignore)]
+    entries: Arc<HashMap<String, String>>,
+    #[reflect(ignore)]
+    layers: Arc<HashMap<String, AnimationLayer>>,
 }
 
 impl Default for AnimationTree {
@@ -70,113 +367,734 @@ impl Default for AnimationTree {
         AnimationTree {
             current: "".to_string(),
             next: "".to_string(),
-            finished: "".to_string(),
             point: Vec2::ZERO,
-            nodes: HashMap::new(),
+            direction: None,
+            frame: 0,
+            elapsed: Duration::ZERO,
+            shown: None,
+            entered: false,
+            backward: false,
+            flipped: None,
+            stopped: false,
+            wait_end: false,
+            pending: Vec::new(),
+            parameters: HashMap::new(),
+            blend_parameter: None,
+            speed: 1.,
+            nodes: Arc::new(HashMap::new()),
+            entries: Arc::new(HashMap::new()),
+            layers: Arc::new(HashMap::new()),
         }
     }
 }
 
 impl AnimationTree {
     pub fn from(nodes: HashMap<String, AnimationNodeMachine>, default: String) -> Self {
+        AnimationTree::from_machine(AnimationSubMachine::from(nodes, default))
+    }
+
+    /// Build the tree from the graph of `machine` and the sub-machines nested in it.
+    pub fn from_machine(machine: AnimationSubMachine) -> Self {
+        let mut nodes = HashMap::new();
+        let mut entries = HashMap::new();
+        machine.flatten("", &mut Vec::new(), &[], &mut nodes, &mut entries);
+
         AnimationTree {
-            current: default,
+            current: machine.root_entry(),
             next: "".to_string(),
-            finished: "".to_string(),
             point: Vec2::ZERO,
-            nodes,
+            direction: None,
+            frame: 0,
+            elapsed: Duration::ZERO,
+            shown: None,
+            entered: false,
+            backward: false,
+            flipped: None,
+            stopped: false,
+            wait_end: false,
+            pending: Vec::new(),
+            parameters: HashMap::new(),
+            blend_parameter: None,
+            speed: 1.,
+            nodes: Arc::new(nodes),
+            entries: Arc::new(entries),
+            layers: Arc::new(HashMap::new()),
+        }
+    }
+
+    /// Declare `name` with its default value.
+    pub fn parameter(mut self, name: String, value: AnimationParameter) -> Self {
+        self.parameters.insert(name, value);
+        self
+    }
+
+    /// Take the blend point from the `Vec2` parameter `name`.
+    pub fn blend_parameter(mut self, name: String) -> Self {
+        self.blend_parameter = Some(name);
+        self
+    }
+
+    /// Declare the layer `name`, drawn by the `AnimationLayerSprite` children of the entity.
+    pub fn layer(mut self, name: String, layer: AnimationLayer) -> Self {
+        Arc::make_mut(&mut self.layers).insert(name, layer);
+        self
+    }
+
+    pub fn get_parameter(&self, name: &str) -> Option<&AnimationParameter> {
+        self.parameters.get(name)
+    }
+
+    pub fn set_parameter(&mut self, name: &str, value: AnimationParameter) {
+        self.parameters.insert(name.to_string(), value);
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.set_parameter(name, AnimationParameter::Float(value));
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.set_parameter(name, AnimationParameter::Bool(value));
+    }
+
+    pub fn set_vec2(&mut self, name: &str, value: Vec2) {
+        self.set_parameter(name, AnimationParameter::Vec2(value));
+    }
+
+    /// Set the trigger `name` until a transition uses it.
+    pub fn set_trigger(&mut self, name: &str) {
+        self.set_parameter(name, AnimationParameter::Trigger(true));
+    }
+
+    pub fn nodes(&self) -> &HashMap<String, AnimationNodeMachine> {
+        &self.nodes
+    }
+
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    /// Node waiting to be entered, empty when none.
+    pub fn next(&self) -> &str {
+        &self.next
+    }
+
+    pub fn point(&self) -> Vec2 {
+        self.point
+    }
+
+    pub fn parameters(&self) -> &HashMap<String, AnimationParameter> {
+        &self.parameters
+    }
+
+    /// Index of the point of the current node playing.
+    pub fn direction(&self) -> Option<usize> {
+        self.direction
+    }
+
+    /// Index of the frame playing in the current animation.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Whether the current animation is mirrored.
+    pub fn is_flipped(&self) -> bool {
+        self.nodes
+            .get(&self.current)
+            .map_or(false, |node| node.flip_x(self.direction))
+    }
+
+    pub fn layers(&self) -> &HashMap<String, AnimationLayer> {
+        &self.layers
+    }
+
+    /// Whether the current node is `name`, or one of the nodes of the sub-machine `name`.
+    pub fn is_in(&self, name: &str) -> bool {
+        self.current
+            .strip_prefix(name)
+            .map_or(false, |rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    /// Full name of the node entered by traveling to `name`, the entry node of a sub-machine.
+    fn resolve(&self, name: String) -> String {
+        match self.entries.get(&name) {
+            Some(entry) => entry.clone(),
+            None => name,
         }
     }
 
-    pub fn travel(&mut self, point: Vec2, to_node: String) {
-        let next_node = self.nodes.get(&to_node);
-        if let Some(_) = next_node {
-            self.next = to_node;
+    /// Playing position in frames of `animation`, e.g. `2.5` halfway through the third frame.
+    pub fn position(&self, animation: &SpriteSheetAnimation) -> f32 {
+        let duration = self.frame_duration(animation, self.frame);
+        let progress = if duration.is_zero() {
+            0.
+        } else {
+            (self.elapsed.as_secs_f32() / duration.as_secs_f32()).min(1.)
+        };
+        if self.backward {
+            (self.frame as f32 - progress).max(0.)
+        } else {
+            self.frame as f32 + progress
+        }
+    }
+
+    /// Duration of `frame` of `animation` playing in the current node.
+    fn frame_duration(&self, animation: &SpriteSheetAnimation, frame: usize) -> Duration {
+        match self.nodes.get(&self.current) {
+            Some(node) => node.frame_duration(self.direction, animation, frame),
+            None => animation
+                .frames
+                .get(frame)
+                .map_or(Duration::ZERO, |f| f.duration),
+        }
+    }
+
+    /// Travel to `to_node`, returns whether the transition from the current node was accepted.
+    ///
+    /// Traveling to a sub-machine enters its entry node. An `AtEnd` transition from a looping
+    /// node is rejected, the node never ends.
+    pub fn travel(&mut self, point: Vec2, to_node: String) -> bool {
+        let to_node = self.resolve(to_node);
+        if !self.nodes.contains_key(&to_node) {
+            return false;
+        }
+
+        let condition = match self.nodes.get(&self.current) {
+            Some(current_node) if to_node != self.current => current_node.condition(&to_node),
+            _ => Some(TransitionCondition::Immediate),
+        };
+        match condition {
+            None => return false,
+            Some(TransitionCondition::Immediate) => self.wait_end = false,
+            Some(TransitionCondition::FromFrame(frame)) => {
+                if self.frame < frame {
+                    return false;
+                }
+                self.wait_end = false;
+            }
+            Some(TransitionCondition::AtEnd) => {
+                // A looping node never reaches its end, a stopped one already did.
+                let looping = self.nodes.get(&self.current).map_or(false, |current_node| {
+                    current_node.mode == AnimationMode::Repeat
+                });
+                if looping && !self.stopped {
+                    return false;
+                }
+                self.wait_end = !self.stopped;
+            }
+        }
+
+        self.next = to_node;
+        self.point = point;
+        self.pending.clear();
+        true
+    }
+
+    /// Enter `to_node` regardless of the transitions of the current node, e.g. from a debug tool.
+    pub fn jump(&mut self, point: Vec2, to_node: String) -> bool {
+        let to_node = self.resolve(to_node);
+        if !self.nodes.contains_key(&to_node) {
+            return false;
+        }
+
+        self.next = to_node;
+        self.point = point;
+        self.wait_end = false;
+        self.pending.clear();
+        true
+    }
+
+    /// Travel to `to_node` through the shortest path of the graph.
+    ///
+    /// Every intermediate node plays to completion before the next one, so they should not loop.
+    pub fn travel_path(&mut self, point: Vec2, to_node: String) -> bool {
+        let to_node = self.resolve(to_node);
+        let mut path = match self.find_path(&to_node) {
+            Some(path) if !path.is_empty() => path,
+            // Already there, or unreachable.
+            _ => return self.travel(point, to_node),
+        };
+
+        let first = path.pop_front().unwrap();
+        let is_transition = self.nodes.get(&self.current).map_or(true, |current_node| {
+            current_node.condition(&first).is_some()
+        });
+        if is_transition {
+            if !self.travel(point, first) {
+                return false;
+            }
+        } else if self.stopped {
+            // Finished without advancing, leave right away instead of waiting for it.
+            self.next = first;
+            self.point = point;
+            self.wait_end = false;
+        } else {
+            // Only reachable by the auto-advance, wait for the current node to finish.
             self.point = point;
+            path.push_front(first);
+        }
+
+        self.pending = path.into();
+        true
+    }
+
+    /// Nodes still waiting to be played by `travel_path`, in order.
+    pub fn pending(&self) -> &[String] {
+        &self.pending
+    }
+
+    pub fn clear_pending(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Breadth-first search of the nodes to go through to reach `to_node`, without the current one.
+    fn find_path(&self, to_node: &str) -> Option<VecDeque<String>> {
+        if !self.nodes.contains_key(to_node) {
+            return None;
+        }
+        if to_node == self.current {
+            return Some(VecDeque::new());
+        }
+
+        let mut visited: HashSet<&String> = HashSet::default();
+        let mut parents: HashMap<&String, &String> = HashMap::default();
+        let mut queue: VecDeque<&String> = VecDeque::new();
+        visited.insert(&self.current);
+        queue.push_back(&self.current);
+
+        while let Some(node) = queue.pop_front() {
+            if node == to_node {
+                let mut path = VecDeque::new();
+                let mut node = node;
+                while node != &self.current {
+                    path.push_front(node.clone());
+                    node = parents[node];
+                }
+                return Some(path);
+            }
+
+            if let Some(node_machine) = self.nodes.get(node) {
+                for neighbor in node_machine.neighbors(&self.nodes) {
+                    if visited.insert(neighbor) {
+                        parents.insert(neighbor, node);
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Snapshot of the playing state, to resume it on a tree instanced from `template`.
+    pub fn save_state(&self, template: String) -> AnimationTreeState {
+        AnimationTreeState {
+            template,
+            current: self.current.clone(),
+            point: self.point,
+            direction: self.direction,
+            frame: self.frame,
+            elapsed: self.elapsed.as_secs_f32(),
+            backward: self.backward,
+            stopped: self.stopped,
+            parameters: self.parameters.clone(),
+            speed: self.speed,
+            next: self.next.clone(),
+            pending: self.pending.clone(),
+            wait_end: self.wait_end,
+        }
+    }
+
+    /// Resume the playing state saved from a tree of the same template.
+    pub fn restore_state(&mut self, state: &AnimationTreeState) {
+        if self.nodes.contains_key(&state.current) {
+            self.current = state.current.clone();
+        }
+        // Nodes the template may have lost since the save are dropped.
+        self.next = if self.nodes.contains_key(&state.next) {
+            state.next.clone()
+        } else {
+            "".to_string()
+        };
+        self.point = state.point;
+        self.direction = state.direction;
+        self.frame = state.frame;
+        // Read from a save file, `Duration` panics on negative, infinite or NaN seconds.
+        self.elapsed = if state.elapsed.is_finite() {
+            Duration::from_secs_f32(state.elapsed.clamp(0., u32::MAX as f32))
+        } else {
+            Duration::ZERO
+        };
+        // The frame was shown before saving, do not send its markers again.
+        self.shown = Some(state.frame);
+        self.backward = state.backward;
+        self.stopped = state.stopped;
+        self.wait_end = state.wait_end && !self.next.is_empty();
+        self.pending = state
+            .pending
+            .iter()
+            .filter(|node| self.nodes.contains_key(*node))
+            .cloned()
+            .collect();
+        if state.speed.is_finite() {
+            self.speed = state.speed;
+        }
+        for (name, value) in state.parameters.iter() {
+            self.parameters.insert(name.clone(), *value);
+        }
+    }
+
+    /// Animation of the playing point of the current node.
+    pub(crate) fn animation(&self) -> Option<Animation> {
+        let node = self.nodes.get(&self.current)?;
+        node.points
+            .get(self.direction?)
+            .map(|point| point.animation.clone())
+    }
+
+    /// Replace the nodes with the ones of a reloaded tree, keeping the current state.
+    pub(crate) fn reload(&mut self, tree: &AnimationTree) {
+        self.nodes = tree.nodes.clone();
+        self.entries = tree.entries.clone();
+        self.layers = tree.layers.clone();
+        self.blend_parameter = tree.blend_parameter.clone();
+        for (name, value) in tree.parameters.iter() {
+            self.parameters.entry(name.clone()).or_insert(*value);
+        }
+        if !self.nodes.contains_key(&self.current) {
+            self.current = tree.current.clone();
+        }
+        // Let `next_animation` pick the animation again from the new points.
+        self.next = self.current.clone();
+        self.pending.clear();
+    }
+
+    /// Follow the blend parameter and fire the first transition of the current node whose
+    /// conditions hold.
+    pub(crate) fn evaluate_parameters(&mut self) {
+        let point = self
+            .blend_parameter
+            .as_ref()
+            .and_then(|name| self.parameters.get(name));
+        if let Some(AnimationParameter::Vec2(point)) = point {
+            if *point != self.point {
+                self.point = *point;
+                if self.next.is_empty() {
+                    self.next = self.current.clone();
+                }
+            }
+        }
+
+        let transition = self.nodes.get(&self.current).and_then(|node| {
+            node.transitions
+                .iter()
+                .chain(node.inherited.iter())
+                .find(|transition| {
+                    !transition.when.is_empty()
+                        && transition.when.iter().all(|condition| {
+                            condition.holds(self.parameters.get(condition.parameter()))
+                        })
+                })
+                .cloned()
+        });
+        if let Some(transition) = transition {
+            if self.travel(self.point, transition.to) {
+                for condition in transition.when.iter() {
+                    if let ParameterCondition::Trigger(name) = condition {
+                        self.set_parameter(name, AnimationParameter::Trigger(false));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Enter `node`, playing the animation picked for the current blend point from its start.
+    fn enter(&mut self, node: String) -> Option<Animation> {
+        let (direction, animation) = self.nodes.get(&node)?.play(self.point, None);
+        self.current = node;
+        self.direction = Some(direction);
+        self.restart();
+        self.entered = true;
+        Some(animation)
+    }
+
+    fn restart(&mut self) {
+        self.frame = 0;
+        self.elapsed = Duration::ZERO;
+        self.shown = None;
+        self.backward = false;
+        self.stopped = false;
+    }
+
+    /// Move the playing position from the frames lasting `from` to the same phase of `to`.
+    fn switch_direction(
+        &mut self,
+        switch: DirectionSwitch,
+        from: Option<&[Duration]>,
+        to: Option<&[Duration]>,
+    ) {
+        match (switch, from, to) {
+            (DirectionSwitch::KeepFrame, _, _) => {}
+            (DirectionSwitch::KeepTime, Some(from), Some(to)) => {
+                let played = from.iter().take(self.frame).sum::<Duration>() + self.elapsed;
+                let from_total = from.iter().sum::<Duration>().as_secs_f32();
+                let phase = if from_total > 0. {
+                    played.as_secs_f32() / from_total
+                } else {
+                    0.
+                };
+
+                let mut remaining = to.iter().sum::<Duration>().mul_f32(phase.clamp(0., 1.));
+                self.frame = 0;
+                for duration in to.iter() {
+                    if remaining < *duration || self.frame + 1 == to.len() {
+                        break;
+                    }
+                    remaining -= *duration;
+                    self.frame += 1;
+                }
+                self.elapsed = remaining;
+            }
+            _ => self.restart(),
+        }
+    }
+
+    /// Mirror `sprite` when the flip of the playing point changed since it was last written.
+    fn flip(&mut self, sprite: &mut Mut<TextureAtlasSprite>) {
+        let flip_x = self.is_flipped();
+        if self.flipped == Some(flip_x) {
+            return;
+        }
+        self.flipped = Some(flip_x);
+        if sprite.flip_x != flip_x {
+            sprite.flip_x = flip_x;
+        }
+    }
+
+    /// Show `frame` and send the markers it carries.
+    fn show(&mut self, entity: Entity, frame: usize, markers: &mut EventWriter<AnimationMarker>) {
+        self.frame = frame;
+        if self.shown == Some(frame) {
+            return;
+        }
+        self.shown = Some(frame);
+
+        if let Some(node) = self.nodes.get(&self.current) {
+            for (_, marker) in node.markers.iter().filter(|(f, _)| *f == frame) {
+                markers.send(AnimationMarker {
+                    entity,
+                    node: self.current.clone(),
+                    marker: marker.clone(),
+                });
+            }
         }
     }
 }
 
+/// Fixed time every update advances the animations by, instead of the frame time.
+///
+/// Keeps the animations deterministic, e.g. for replays and tests.
+#[derive(Default)]
+pub struct AnimationTimestep(pub Option<Duration>);
+
 pub(crate) fn create_animation(
     mut commands: Commands,
-    mut animation: Query<
-        (Entity, &mut AnimationTree),
-        (Without<Play>, Without<Handle<SpriteSheetAnimation>>),
-    >,
+    mut started: EventWriter<AnimationStarted>,
+    mut animation: Query<(Entity, &mut AnimationTree), Without<Handle<SpriteSheetAnimation>>>,
 ) {
-    for (entity, animation_tree) in animation.iter_mut() {
-        if let Some(animation) = animation_tree.nodes.get(&animation_tree.current) {
-            let animation = animation.play(animation_tree.point);
-            commands
-                .entity(entity)
-                .insert(animation.handle)
-                .insert(Play);
+    for (entity, mut animation_tree) in animation.iter_mut() {
+        let current = animation_tree.current.clone();
+        if let Some(animation) = animation_tree.enter(current) {
+            commands.entity(entity).insert(animation.handle);
+            started.send(AnimationStarted {
+                entity,
+                node: animation_tree.current.clone(),
+                direction: animation_tree.point,
+            });
         }
     }
 }
 
 pub(crate) fn next_animation(
-    mut query: Query<
-        (
-            &mut Handle<SpriteSheetAnimation>,
-            &mut SpriteSheetAnimationState,
-            &mut AnimationTree,
-        ),
-        With<Play>,
-    >,
+    animations: Res<Assets<SpriteSheetAnimation>>,
+    mut started: EventWriter<AnimationStarted>,
+    mut query: Query<(
+        Entity,
+        &mut Handle<SpriteSheetAnimation>,
+        &mut AnimationTree,
+    )>,
 ) {
-    for (mut animation, mut animation_state, mut animation_tree) in query.iter_mut() {
-        let next_node = animation_tree.nodes.get(&animation_tree.next);
-        if let Some(next_node) = next_node {
-            let next_animation = next_node.play(animation_tree.point);
-            if *animation != next_animation.handle {
+    for (entity, mut animation, mut animation_tree) in query.iter_mut() {
+        if animation_tree.wait_end || !animation_tree.nodes.contains_key(&animation_tree.next) {
+            continue;
+        }
+        let next = std::mem::take(&mut animation_tree.next);
+
+        if next != animation_tree.current {
+            if let Some(next_animation) = animation_tree.enter(next) {
                 *animation = next_animation.handle;
-                animation_state.reset();
+                started.send(AnimationStarted {
+                    entity,
+                    node: animation_tree.current.clone(),
+                    direction: animation_tree.point,
+                });
             }
-            animation_tree.current = animation_tree.next.clone();
-            animation_tree.next = "".to_string();
+            continue;
+        }
+
+        // Same node, only the blend point may pick another animation.
+        let nodes = animation_tree.nodes.clone();
+        let next_node = &nodes[&next];
+        let previous = animation_tree.direction;
+        let (direction, next_animation) = next_node.play(animation_tree.point, previous);
+        animation_tree.direction = Some(direction);
+        if *animation != next_animation.handle {
+            let from = animations
+                .get(&*animation)
+                .map(|from| next_node.durations(previous, from));
+            let to = animations
+                .get(&next_animation.handle)
+                .map(|to| next_node.durations(Some(direction), to));
+            animation_tree.switch_direction(
+                next_node.direction_switch,
+                from.as_deref(),
+                to.as_deref(),
+            );
+            *animation = next_animation.handle;
         }
     }
 }
 
 pub(crate) fn animation_tree(
-    mut commands: Commands,
-    removed: RemovedComponents<Play>,
-    mut query: Query<(&mut Handle<SpriteSheetAnimation>, &mut AnimationTree)>,
+    time: Res<Time>,
+    timestep: Res<AnimationTimestep>,
+    animations: Res<Assets<SpriteSheetAnimation>>,
+    mut started: EventWriter<AnimationStarted>,
+    mut finished: EventWriter<AnimationFinished>,
+    mut markers: EventWriter<AnimationMarker>,
+    mut query: Query<(
+        Entity,
+        &mut Handle<SpriteSheetAnimation>,
+        &mut TextureAtlasSprite,
+        &mut AnimationTree,
+    )>,
 ) {
-    for entity in removed.iter() {
-        commands.entity(entity).insert(Play);
-        if let Ok((mut animation, mut animation_tree)) = query.get_mut(entity) {
-            animation_tree.finished = animation_tree.current.clone();
-            let next_node = animation_tree.nodes.get(&animation_tree.next);
-            match next_node {
-                Some(next_node) => {
-                    let next_animation = next_node.play(animation_tree.point);
-                    if *animation != next_animation.handle {
-                        *animation = next_animation.handle;
-                    }
-                    animation_tree.current = animation_tree.next.clone();
-                    animation_tree.next = "".to_string();
+    for (entity, mut animation, mut sprite, mut animation_tree) in query.iter_mut() {
+        let sprite_animation = match animations.get(&*animation) {
+            Some(sprite_animation) if !sprite_animation.frames.is_empty() => sprite_animation,
+            _ => continue,
+        };
+        let frames = &sprite_animation.frames;
+        if animation_tree.stopped {
+            continue;
+        }
+
+        let (mode, playback, random_start, speed) =
+            animation_tree.nodes.get(&animation_tree.current).map_or(
+                (AnimationMode::Repeat, AnimationPlayback::Forward, false, 1.),
+                |node| (node.mode, node.playback, node.random_start, node.speed),
+            );
+        animation_tree.flip(&mut sprite);
+        // Nothing shown yet, the animation starts.
+        if animation_tree.shown.is_none() {
+            animation_tree.frame = playback.first_frame(frames.len());
+            animation_tree.backward = playback == AnimationPlayback::Reverse;
+            // Only on entering the node, not when a direction switch restarts it.
+            if std::mem::take(&mut animation_tree.entered) && random_start {
+                let mut rng = rand::thread_rng();
+                animation_tree.frame = rng.gen_range(0..frames.len());
+                animation_tree.elapsed = animation_tree
+                    .frame_duration(sprite_animation, animation_tree.frame)
+                    .mul_f32(rng.gen());
+            }
+        }
+        let mut frame = animation_tree.frame.min(frames.len() - 1);
+        animation_tree.show(entity, frame, &mut markers);
+
+        let mut completed = false;
+        let delta = timestep.0.unwrap_or_else(|| time.delta());
+        // `mul_f32` panics on NaN, negative and overflowing factors, `max` also turns NaN into 0.
+        let speed = (speed * animation_tree.speed).max(0.).min(MAX_SPEED);
+        animation_tree.elapsed += delta.mul_f32(speed);
+        // Frames without duration are skipped, a ping-pong cycle of them all stops the loop.
+        let mut skipped = 0;
+        loop {
+            let duration = animation_tree.frame_duration(sprite_animation, frame);
+            if duration.is_zero() {
+                skipped += 1;
+                if skipped > 2 * frames.len() {
+                    break;
+                }
+            } else if animation_tree.elapsed < duration {
+                break;
+            } else {
+                animation_tree.elapsed -= duration;
+                skipped = 0;
+            }
+            match playback.advance(frame, animation_tree.backward, frames.len(), mode) {
+                Some((next_frame, backward)) => {
+                    frame = next_frame;
+                    animation_tree.backward = backward;
                 }
                 None => {
-                    if let Some(current_node) = animation_tree.nodes.get(&animation_tree.current) {
-                        match &current_node.next {
-                            Some(next) => {
-                                let next_node = animation_tree.nodes.get(next);
-                                if let Some(next_node) = next_node {
-                                    let next_animation = next_node.play(animation_tree.point);
-                                    if *animation != next_animation.handle {
-                                        *animation = next_animation.handle;
-                                    }
-                                    animation_tree.current = animation_tree.next.clone();
-                                    animation_tree.next = "".to_string();
-                                }
-                            }
-                            None => {}
-                        }
+                    completed = true;
+                    break;
+                }
+            }
+            animation_tree.show(entity, frame, &mut markers);
+        }
+        if sprite.index != frames[frame].index {
+            sprite.index = frames[frame].index;
+        }
+
+        if !completed {
+            continue;
+        }
+        finished.send(AnimationFinished {
+            entity,
+            node: animation_tree.current.clone(),
+            direction: animation_tree.point,
+        });
+
+        // An explicit travel wins over a path in progress, which wins over the node's own `next`.
+        let next = if animation_tree.nodes.contains_key(&animation_tree.next) {
+            Some(std::mem::take(&mut animation_tree.next))
+        } else if !animation_tree.pending.is_empty() {
+            Some(animation_tree.pending.remove(0))
+        } else {
+            animation_tree
+                .nodes
+                .get(&animation_tree.current)
+                .and_then(|current_node| current_node.next.clone())
+        };
+        animation_tree.wait_end = false;
+
+        match next.and_then(|next| animation_tree.enter(next)) {
+            Some(next_animation) => {
+                let playback = animation_tree
+                    .nodes
+                    .get(&animation_tree.current)
+                    .map_or(AnimationPlayback::Forward, |node| node.playback);
+                if let Some(first) =
+                    animations
+                        .get(&next_animation.handle)
+                        .and_then(|next_animation| {
+                            let first = playback.first_frame(next_animation.frames.len());
+                            next_animation.frames.get(first)
+                        })
+                {
+                    if sprite.index != first.index {
+                        sprite.index = first.index;
                     }
                 }
+                animation_tree.flip(&mut sprite);
+                *animation = next_animation.handle;
+                started.send(AnimationStarted {
+                    entity,
+                    node: animation_tree.current.clone(),
+                    direction: animation_tree.point,
+                });
             }
+            None => animation_tree.stopped = true,
         }
     }
 }
@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use benimator::{Frame, SpriteSheetAnimation};
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    sprite::Rect,
+    utils::{BoxedFuture, HashMap},
+};
+use serde::Deserialize;
+
+/// Sprite sheet exported by Aseprite as `.aseprite.json`, with the array or the hash layout.
+///
+/// The atlas is the `atlas` labeled asset and the animation of a frame tag the `tags/{name}` one,
+/// e.g. `Player/Player.aseprite.json#atlas`. Sprites have no anchor to put trimmed frames back on
+/// their offset, sheets with trimmed or rotated frames fail to load.
+#[derive(TypeUuid)]
+#[uuid = "6a1c8f4e-2d7b-4f3a-9e5c-8b0d1f2a3c4e"]
+pub struct AsepriteSheet {
+    pub atlas: Handle<TextureAtlas>,
+    /// Animation of each frame tag, by tag name.
+    pub tags: HashMap<String, Handle<SpriteSheetAnimation>>,
+}
+
+#[derive(Deserialize)]
+struct AsepriteDescriptor {
+    frames: AsepriteFrames,
+    meta: AsepriteMeta,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AsepriteFrames {
+    Array(Vec<AsepriteFrame>),
+    /// Frames by file name, in the order of the export.
+    Hash(serde_json::Map<String, serde_json::Value>),
+}
+
+#[derive(Deserialize)]
+struct AsepriteFrame {
+    frame: AsepriteRect,
+    #[serde(default)]
+    trimmed: bool,
+    #[serde(default)]
+    rotated: bool,
+    /// Milliseconds.
+    duration: u64,
+}
+
+#[derive(Deserialize)]
+struct AsepriteRect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+#[derive(Deserialize)]
+struct AsepriteSize {
+    w: f32,
+    h: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AsepriteMeta {
+    image: String,
+    size: AsepriteSize,
+    #[serde(default)]
+    frame_tags: Vec<AsepriteTag>,
+}
+
+#[derive(Deserialize)]
+struct AsepriteTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: AsepriteDirection,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AsepriteDirection {
+    #[default]
+    Forward,
+    Reverse,
+    Pingpong,
+    PingpongReverse,
+}
+
+impl AsepriteTag {
+    /// Frame indices in playing order.
+    fn indices(&self) -> Vec<usize> {
+        let forward = self.from..=self.to;
+        let back = (self.from + 1..self.to).rev();
+        match self.direction {
+            AsepriteDirection::Forward => forward.collect(),
+            AsepriteDirection::Reverse => forward.rev().collect(),
+            AsepriteDirection::Pingpong => forward.chain(back).collect(),
+            AsepriteDirection::PingpongReverse => {
+                forward.rev().chain(self.from + 1..self.to).collect()
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct AsepriteLoader;
+
+impl AssetLoader for AsepriteLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let descriptor: AsepriteDescriptor = serde_json::from_slice(bytes)?;
+            let frames = match descriptor.frames {
+                AsepriteFrames::Array(frames) => frames,
+                AsepriteFrames::Hash(frames) => frames
+                    .into_iter()
+                    .map(|(_, frame)| serde_json::from_value(frame))
+                    .collect::<Result<_, _>>()?,
+            };
+            if let Some(index) = frames
+                .iter()
+                .position(|frame| frame.trimmed || frame.rotated)
+            {
+                anyhow::bail!(
+                    "frame {} is trimmed or rotated, export the sheet untrimmed and unrotated",
+                    index
+                );
+            }
+            // A frame without duration would be shown forever.
+            if let Some(index) = frames.iter().position(|frame| frame.duration == 0) {
+                anyhow::bail!("frame {} has a duration of 0 milliseconds", index);
+            }
+
+            // Aseprite names the image relative to the exported file.
+            let image_path = AssetPath::new(
+                load_context.path().with_file_name(&descriptor.meta.image),
+                None,
+            );
+            let mut atlas = TextureAtlas::new_empty(
+                load_context.get_handle(image_path.clone()),
+                Vec2::new(descriptor.meta.size.w, descriptor.meta.size.h),
+            );
+            for AsepriteFrame { frame, .. } in frames.iter() {
+                atlas.add_texture(Rect {
+                    min: Vec2::new(frame.x, frame.y),
+                    max: Vec2::new(frame.x + frame.w, frame.y + frame.h),
+                });
+            }
+            let atlas = load_context
+                .set_labeled_asset("atlas", LoadedAsset::new(atlas).with_dependency(image_path));
+
+            let mut tags = HashMap::new();
+            for tag in descriptor.meta.frame_tags.iter() {
+                let animation = SpriteSheetAnimation::from_frames(
+                    tag.indices()
+                        .into_iter()
+                        .filter_map(|index| {
+                            let duration = Duration::from_millis(frames.get(index)?.duration);
+                            Some(Frame::new(index, duration))
+                        })
+                        .collect(),
+                );
+                let handle = load_context
+                    .set_labeled_asset(&format!("tags/{}", tag.name), LoadedAsset::new(animation));
+                tags.insert(tag.name.clone(), handle);
+            }
+
+            load_context.set_default_asset(LoadedAsset::new(AsepriteSheet { atlas, tags }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["aseprite.json"]
+    }
+}
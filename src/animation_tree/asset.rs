@@ -0,0 +1,353 @@
+use std::{collections::HashMap as StdHashMap, ops::RangeInclusive, time::Duration};
+
+use benimator::SpriteSheetAnimation;
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use super::{
+    Animation, AnimationLayer, AnimationMode, AnimationNode, AnimationNodeMachine,
+    AnimationParameter, AnimationPlayback, AnimationSubMachine, AnimationTrack,
+    AnimationTransition, AnimationTree, BlendSpace, DirectionSwitch,
+};
+
+/// Animation tree loaded from a `.animtree.ron` file.
+#[derive(TypeUuid)]
+#[uuid = "0b0f5b3e-6b8e-4c4e-9d1f-3f2a7c1d5e21"]
+pub struct AnimationTreeAsset {
+    pub tree: AnimationTree,
+}
+
+#[derive(Deserialize)]
+struct AnimationTreeDescriptor {
+    default: String,
+    /// `.aseprite.json` sheet providing the `tag` animations, relative to the tree file.
+    #[serde(default)]
+    sheet: Option<String>,
+    #[serde(default)]
+    parameters: StdHashMap<String, AnimationParameter>,
+    #[serde(default)]
+    blend_parameter: Option<String>,
+    nodes: StdHashMap<String, AnimationNodeMachineDescriptor>,
+    #[serde(default)]
+    machines: StdHashMap<String, AnimationSubMachineDescriptor>,
+    /// Frames of each layer, by full node name then by point of the node.
+    #[serde(default)]
+    layers: StdHashMap<String, StdHashMap<String, Vec<RangeInclusive<usize>>>>,
+}
+
+#[derive(Deserialize)]
+struct AnimationSubMachineDescriptor {
+    entry: String,
+    nodes: StdHashMap<String, AnimationNodeMachineDescriptor>,
+    #[serde(default)]
+    machines: StdHashMap<String, AnimationSubMachineDescriptor>,
+    #[serde(default)]
+    exit: Option<String>,
+    #[serde(default)]
+    next: Option<String>,
+    #[serde(default)]
+    transitions: Vec<AnimationTransition>,
+}
+
+#[derive(Deserialize)]
+struct AnimationNodeMachineDescriptor {
+    points: Vec<AnimationNodeDescriptor>,
+    /// Seconds of each frame for the points without their own `durations`.
+    #[serde(default)]
+    durations: Vec<f32>,
+    #[serde(default)]
+    mode: AnimationMode,
+    #[serde(default)]
+    playback: AnimationPlayback,
+    #[serde(default)]
+    random_start: bool,
+    #[serde(default)]
+    direction_switch: DirectionSwitch,
+    #[serde(default = "default_speed")]
+    speed: f32,
+    #[serde(default)]
+    next: Option<String>,
+    #[serde(default)]
+    markers: Vec<(usize, String)>,
+    #[serde(default)]
+    transitions: Vec<AnimationTransition>,
+    #[serde(default)]
+    tracks: Vec<AnimationTrack>,
+    #[serde(default)]
+    blend: BlendSpace,
+}
+
+#[derive(Deserialize)]
+struct AnimationNodeDescriptor {
+    #[serde(default)]
+    frames: Option<RangeInclusive<usize>>,
+    /// Frame tag of the sheet, playing with the durations of the sheet.
+    #[serde(default)]
+    tag: Option<String>,
+    #[serde(default = "default_frame_duration")]
+    duration: f32,
+    /// Seconds of each frame, overriding `duration` and the durations of the tag.
+    #[serde(default)]
+    durations: Vec<f32>,
+    point: Vec2,
+    #[serde(default)]
+    flip_x: bool,
+}
+
+fn default_frame_duration() -> f32 {
+    0.1
+}
+
+fn default_speed() -> f32 {
+    1.
+}
+
+/// `value` seconds as a `Duration`, an error naming the point `index` of `name` when zero,
+/// negative, infinite or NaN.
+fn seconds(value: f32, name: &str, index: usize) -> Result<Duration, anyhow::Error> {
+    // A frame without duration would be shown forever.
+    if !(value > 0. && value <= u32::MAX as f32) {
+        anyhow::bail!(
+            "point {} of node `{}` has an invalid duration of {} seconds",
+            index,
+            name,
+            value
+        );
+    }
+    Ok(Duration::from_secs_f32(value))
+}
+
+/// Build the node `name`, registering its animations as labeled assets.
+fn load_node(
+    load_context: &mut LoadContext,
+    sheet: Option<&AssetPath>,
+    name: &str,
+    machine: AnimationNodeMachineDescriptor,
+) -> Result<AnimationNodeMachine, anyhow::Error> {
+    if !(machine.speed.is_finite() && machine.speed >= 0.) {
+        anyhow::bail!("node `{}` has an invalid speed of {}", name, machine.speed);
+    }
+    let mut points = Vec::new();
+    // Mirrored points play the same frames, share their animation.
+    let mut animations = StdHashMap::new();
+    for (index, node) in machine.points.into_iter().enumerate() {
+        let handle = match (node.tag, node.frames) {
+            (Some(tag), _) => {
+                let sheet = sheet.ok_or_else(|| {
+                    anyhow::anyhow!("node `{}` plays the tag `{}` without a `sheet`", name, tag)
+                })?;
+                load_context.get_handle(AssetPath::new(
+                    sheet.path().to_path_buf(),
+                    Some(format!("tags/{}", tag)),
+                ))
+            }
+            (None, Some(frames)) => {
+                let key = (*frames.start(), *frames.end(), node.duration.to_bits());
+                match animations.get(&key) {
+                    Some(handle) => Handle::clone(handle),
+                    None => {
+                        let animation = SpriteSheetAnimation::from_range(
+                            frames,
+                            seconds(node.duration, name, index)?,
+                        );
+                        let handle = load_context.set_labeled_asset(
+                            &format!("{}/{}", name, index),
+                            LoadedAsset::new(animation),
+                        );
+                        animations.insert(key, handle.clone());
+                        handle
+                    }
+                }
+            }
+            (None, None) => {
+                anyhow::bail!(
+                    "point {} of node `{}` has neither `frames` nor `tag`",
+                    index,
+                    name
+                )
+            }
+        };
+
+        let mut point = AnimationNode::from(Animation::from(handle), node.point);
+        if node.flip_x {
+            point = point.flip_x();
+        }
+        let durations = if node.durations.is_empty() {
+            &machine.durations
+        } else {
+            &node.durations
+        };
+        if !durations.is_empty() {
+            point = point.durations(
+                durations
+                    .iter()
+                    .map(|duration| seconds(*duration, name, index))
+                    .collect::<Result<_, _>>()?,
+            );
+        }
+        points.push(point);
+    }
+
+    let mut node_machine = AnimationNodeMachine::from(points)
+        .mode(machine.mode)
+        .playback(machine.playback)
+        .direction_switch(machine.direction_switch)
+        .speed(machine.speed)
+        .blend(machine.blend);
+    if machine.random_start {
+        node_machine = node_machine.random_start();
+    }
+    if let Some(next) = machine.next {
+        node_machine = node_machine.next(next);
+    }
+    for (frame, marker) in machine.markers {
+        node_machine = node_machine.marker(frame, marker);
+    }
+    for transition in machine.transitions {
+        node_machine = node_machine.transition(transition);
+    }
+    for track in machine.tracks {
+        node_machine = node_machine.track(track);
+    }
+    Ok(node_machine)
+}
+
+/// Build the graph of `nodes` and the nested `machines`, named under `prefix`.
+fn load_machine(
+    load_context: &mut LoadContext,
+    sheet: Option<&AssetPath>,
+    prefix: &str,
+    entry: String,
+    nodes: StdHashMap<String, AnimationNodeMachineDescriptor>,
+    machines: StdHashMap<String, AnimationSubMachineDescriptor>,
+) -> Result<AnimationSubMachine, anyhow::Error> {
+    let nodes = nodes
+        .into_iter()
+        .map(|(name, machine)| {
+            let node = load_node(load_context, sheet, &format!("{}{}", prefix, name), machine)?;
+            Ok((name, node))
+        })
+        .collect::<Result<_, anyhow::Error>>()?;
+
+    let mut sub_machine = AnimationSubMachine::from(nodes, entry);
+    for (name, machine) in machines {
+        let mut nested = load_machine(
+            load_context,
+            sheet,
+            &format!("{}{}/", prefix, name),
+            machine.entry,
+            machine.nodes,
+            machine.machines,
+        )?;
+        if let Some(exit) = machine.exit {
+            nested = nested.exit(exit);
+        }
+        if let Some(next) = machine.next {
+            nested = nested.next(next);
+        }
+        for transition in machine.transitions {
+            nested = nested.transition(transition);
+        }
+        sub_machine = sub_machine.machine(name, nested);
+    }
+    Ok(sub_machine)
+}
+
+#[derive(Default)]
+pub struct AnimationTreeLoader;
+
+impl AssetLoader for AnimationTreeLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let descriptor: AnimationTreeDescriptor = ron::de::from_bytes(bytes)?;
+
+            let sheet = descriptor
+                .sheet
+                .map(|sheet| AssetPath::new(load_context.path().with_file_name(sheet), None));
+            let machine = load_machine(
+                load_context,
+                sheet.as_ref(),
+                "",
+                descriptor.default,
+                descriptor.nodes,
+                descriptor.machines,
+            )?;
+            let mut tree = AnimationTree::from_machine(machine);
+            for (name, nodes) in descriptor.layers {
+                let mut layer = AnimationLayer::default();
+                for (node, points) in nodes {
+                    let points = points
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, frames)| {
+                            // Layers play on the frames of the tree, the duration only times the
+                            // nodes a layer sprite plays on its own.
+                            let animation = SpriteSheetAnimation::from_range(
+                                frames,
+                                Duration::from_secs_f32(default_frame_duration()),
+                            );
+                            Animation::from(load_context.set_labeled_asset(
+                                &format!("layers/{}/{}/{}", name, node, index),
+                                LoadedAsset::new(animation),
+                            ))
+                        })
+                        .collect();
+                    layer = layer.node(node, points);
+                }
+                tree = tree.layer(name, layer);
+            }
+            for (name, value) in descriptor.parameters {
+                tree = tree.parameter(name, value);
+            }
+            if let Some(blend_parameter) = descriptor.blend_parameter {
+                tree = tree.blend_parameter(blend_parameter);
+            }
+            let mut asset = LoadedAsset::new(AnimationTreeAsset { tree });
+            if let Some(sheet) = sheet {
+                asset = asset.with_dependency(sheet);
+            }
+            load_context.set_default_asset(asset);
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["animtree.ron"]
+    }
+}
+
+/// Instance loaded animation trees on their entities, and refresh them when the file changes.
+pub(crate) fn instance_animation_tree(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<AnimationTreeAsset>>,
+    assets: Res<Assets<AnimationTreeAsset>>,
+    pending: Query<(Entity, &Handle<AnimationTreeAsset>), Without<AnimationTree>>,
+    mut instanced: Query<(&Handle<AnimationTreeAsset>, &mut AnimationTree)>,
+) {
+    for (entity, handle) in pending.iter() {
+        if let Some(asset) = assets.get(handle) {
+            commands.entity(entity).insert(asset.tree.clone());
+        }
+    }
+
+    for event in events.iter() {
+        if let AssetEvent::Modified { handle } = event {
+            if let Some(asset) = assets.get(handle) {
+                for (tree_handle, mut animation_tree) in instanced.iter_mut() {
+                    if tree_handle == handle {
+                        animation_tree.reload(&asset.tree);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// Distance under which two points are considered as close as each other.
+const TIE_EPSILON: f32 = 1e-4;
+
+/// Which point wins when the blend point is as close to several of them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+pub enum TieBreak {
+    /// The point declared first.
+    #[default]
+    First,
+    /// The most horizontal point, e.g. left and right on diagonals.
+    Horizontal,
+    /// The most vertical point, e.g. up and down on diagonals.
+    Vertical,
+    /// The point already playing, otherwise the point declared first.
+    Current,
+}
+
+/// Picks the point of a node closest to the blend point.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct BlendSpace {
+    /// How much closer another point has to be before leaving the current one.
+    #[serde(default)]
+    pub hysteresis: f32,
+    #[serde(default)]
+    pub tie_break: TieBreak,
+}
+
+impl BlendSpace {
+    /// Index of the point to play for `point`, `current` being the index already playing.
+    pub fn pick(&self, points: &[Vec2], point: Vec2, current: Option<usize>) -> Option<usize> {
+        let distances: Vec<f32> = points.iter().map(|p| point.distance(*p)).collect();
+        let closest = distances.iter().copied().fold(f32::MAX, f32::min);
+
+        let mut ties = (0..points.len()).filter(|i| distances[*i] - closest <= TIE_EPSILON);
+        let best = match self.tie_break {
+            TieBreak::First => ties.next(),
+            TieBreak::Horizontal => ties.max_by(|a, b| {
+                points[*a]
+                    .x
+                    .abs()
+                    .total_cmp(&points[*b].x.abs())
+                    .then(b.cmp(a))
+            }),
+            TieBreak::Vertical => ties.max_by(|a, b| {
+                points[*a]
+                    .y
+                    .abs()
+                    .total_cmp(&points[*b].y.abs())
+                    .then(b.cmp(a))
+            }),
+            TieBreak::Current => {
+                let ties: Vec<usize> = ties.collect();
+                current
+                    .filter(|current| ties.contains(current))
+                    .or_else(|| ties.first().copied())
+            }
+        }?;
+
+        // Stick with the current point until another one is clearly closer.
+        match current {
+            Some(current)
+                if current < points.len()
+                    && distances[best] + self.hysteresis >= distances[current] =>
+            {
+                Some(current)
+            }
+            _ => Some(best),
+        }
+    }
+}
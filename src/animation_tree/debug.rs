@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+
+use super::AnimationTree;
+
+/// Window showing the live graph of every tree, with buttons to travel or jump to a node.
+pub(crate) fn animation_tree_window(
+    mut egui_context: ResMut<EguiContext>,
+    mut query: Query<(Entity, Option<&Name>, &mut AnimationTree)>,
+) {
+    egui::Window::new("Animation trees").show(egui_context.ctx_mut(), |ui| {
+        for (entity, name, mut animation_tree) in query.iter_mut() {
+            let title = name.map_or_else(|| format!("{:?}", entity), |name| name.to_string());
+            egui::CollapsingHeader::new(title)
+                .id_source(entity)
+                .show(ui, |ui| {
+                    ui.label(format!("next: {}", animation_tree.next()));
+                    ui.label(format!("pending: {:?}", animation_tree.pending()));
+                    ui.label(format!("point: {}", animation_tree.point()));
+                    ui.label(format!(
+                        "direction: {:?}, frame: {}",
+                        animation_tree.direction(),
+                        animation_tree.frame()
+                    ));
+
+                    let mut parameters: Vec<_> = animation_tree.parameters().iter().collect();
+                    parameters.sort_by(|(a, _), (b, _)| a.cmp(b));
+                    for (name, value) in parameters {
+                        ui.label(format!("{}: {:?}", name, value));
+                    }
+                    ui.separator();
+
+                    let mut nodes: Vec<_> = animation_tree.nodes().iter().collect();
+                    nodes.sort_by(|(a, _), (b, _)| a.cmp(b));
+                    let mut travel = None;
+                    let mut jump = None;
+                    for (node, node_machine) in nodes {
+                        let active = node.as_str() == animation_tree.current();
+                        ui.horizontal(|ui| {
+                            if active {
+                                ui.colored_label(egui::Color32::YELLOW, node);
+                            } else {
+                                ui.label(node);
+                            }
+                            if ui.small_button("travel").clicked() {
+                                travel = Some(node.clone());
+                            }
+                            if ui.small_button("jump").clicked() {
+                                jump = Some(node.clone());
+                            }
+                        });
+                        for transition in node_machine.transitions() {
+                            let edge = format!(
+                                "    -> {} {:?} {:?}",
+                                transition.to, transition.condition, transition.when
+                            );
+                            if active {
+                                ui.colored_label(egui::Color32::LIGHT_YELLOW, edge);
+                            } else {
+                                ui.weak(edge);
+                            }
+                        }
+                    }
+
+                    let point = animation_tree.point();
+                    if let Some(node) = travel {
+                        animation_tree.travel(point, node);
+                    }
+                    if let Some(node) = jump {
+                        animation_tree.jump(point, node);
+                    }
+                });
+        }
+    });
+}
@@ -0,0 +1,25 @@
+use bevy::prelude::*;
+
+/// Sent when the animation of `node` reaches a frame carrying `marker`.
+#[derive(Debug, Clone)]
+pub struct AnimationMarker {
+    pub entity: Entity,
+    pub node: String,
+    pub marker: String,
+}
+
+/// Sent when the tree enters `node`, playing the animation picked by `direction`.
+#[derive(Debug, Clone)]
+pub struct AnimationStarted {
+    pub entity: Entity,
+    pub node: String,
+    pub direction: Vec2,
+}
+
+/// Sent when the non-looping animation of `node` plays its last frame.
+#[derive(Debug, Clone)]
+pub struct AnimationFinished {
+    pub entity: Entity,
+    pub node: String,
+    pub direction: Vec2,
+}
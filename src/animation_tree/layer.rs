@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use benimator::{Frame, SpriteSheetAnimation};
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{Animation, AnimationTimestep, AnimationTree, MAX_SPEED};
+
+/// Frames of a composite part of the sprite, e.g. a weapon or a hat, by node of the tree.
+///
+/// A node maps to one animation by point of the node, played on the frame of the tree. Nodes
+/// missing from the layer hide its sprites, so an overlay only declares the nodes it draws. An
+/// overlay may also `play` one of its nodes on its own, over another node of the tree.
+#[derive(Clone, Default)]
+pub struct AnimationLayer {
+    nodes: HashMap<String, Vec<Animation>>,
+}
+
+impl AnimationLayer {
+    /// Draw `points` while the tree plays `node`, in the order of the points of the node.
+    pub fn node(mut self, node: String, points: Vec<Animation>) -> Self {
+        self.nodes.insert(node, points);
+        self
+    }
+
+    fn animation(&self, node: &str, direction: Option<usize>) -> Option<&Animation> {
+        self.nodes.get(node)?.get(direction?)
+    }
+}
+
+#[derive(Clone)]
+struct LayerPlayback {
+    node: String,
+    frame: usize,
+    /// Time spent on `frame`, `None` until the first frame is shown.
+    elapsed: Option<Duration>,
+}
+
+/// Child sprite drawing the `layer` of the `AnimationTree` of its parent, frame by frame.
+///
+/// It follows the node of the tree, unless it `play`s a node of its own, e.g. an upper body
+/// attack while the legs keep running.
+#[derive(Component, Clone)]
+pub struct AnimationLayerSprite {
+    pub layer: String,
+    playback: Option<LayerPlayback>,
+}
+
+impl AnimationLayerSprite {
+    pub fn new(layer: String) -> Self {
+        AnimationLayerSprite {
+            layer,
+            playback: None,
+        }
+    }
+
+    /// Play `node` of the layer once, on the point of the tree, then follow the tree again.
+    pub fn play(&mut self, node: String) {
+        self.playback = Some(LayerPlayback {
+            node,
+            frame: 0,
+            elapsed: None,
+        });
+    }
+
+    /// Node played on its own instead of the node of the tree.
+    pub fn playing(&self) -> Option<&str> {
+        self.playback
+            .as_ref()
+            .map(|playback| playback.node.as_str())
+    }
+}
+
+/// Frame of `playback` once advanced by `delta`, `None` when the animation is over.
+fn advance<'a>(
+    playback: &mut LayerPlayback,
+    frames: &'a [Frame],
+    delta: Duration,
+) -> Option<&'a Frame> {
+    match &mut playback.elapsed {
+        None => playback.elapsed = Some(Duration::ZERO),
+        Some(elapsed) => {
+            *elapsed += delta;
+            while let Some(frame) = frames.get(playback.frame) {
+                if *elapsed < frame.duration {
+                    break;
+                }
+                *elapsed -= frame.duration;
+                playback.frame += 1;
+            }
+        }
+    }
+    frames.get(playback.frame)
+}
+
+pub(crate) fn animation_layer(
+    time: Res<Time>,
+    timestep: Res<AnimationTimestep>,
+    animations: Res<Assets<SpriteSheetAnimation>>,
+    trees: Query<&AnimationTree>,
+    mut sprites: Query<(
+        &Parent,
+        &mut AnimationLayerSprite,
+        &mut TextureAtlasSprite,
+        &mut Visibility,
+    )>,
+) {
+    let delta = timestep.0.unwrap_or_else(|| time.delta());
+    for (parent, mut layer_sprite, mut sprite, mut visibility) in sprites.iter_mut() {
+        let animation_tree = match trees.get(parent.0) {
+            Ok(animation_tree) => animation_tree,
+            Err(_) => continue,
+        };
+        let layer = match animation_tree.layers().get(&layer_sprite.layer) {
+            Some(layer) => layer,
+            None => continue,
+        };
+        let frames = |node: &str| {
+            layer
+                .animation(node, animation_tree.direction())
+                .and_then(|animation| animations.get(animation.handle()))
+                .map(|animation| &animation.frames)
+                .filter(|frames| !frames.is_empty())
+        };
+
+        // `max` also turns NaN into 0, `mul_f32` panics on it.
+        let speed = animation_tree.speed.max(0.).min(MAX_SPEED);
+        let own_frame = match &mut layer_sprite.playback {
+            Some(playback) => frames(&playback.node)
+                .and_then(|own_frames| advance(playback, own_frames, delta.mul_f32(speed))),
+            None => None,
+        };
+        let index = match own_frame {
+            Some(frame) => Some(frame.index),
+            None => {
+                if layer_sprite.playback.is_some() {
+                    layer_sprite.playback = None;
+                }
+                frames(animation_tree.current())
+                    .map(|frames| frames[animation_tree.frame().min(frames.len() - 1)].index)
+            }
+        };
+
+        match index {
+            Some(index) => {
+                if !visibility.is_visible {
+                    visibility.is_visible = true;
+                }
+                if sprite.index != index {
+                    sprite.index = index;
+                }
+                if sprite.flip_x != animation_tree.is_flipped() {
+                    sprite.flip_x = animation_tree.is_flipped();
+                }
+            }
+            None => {
+                if visibility.is_visible {
+                    visibility.is_visible = false;
+                }
+            }
+        }
+    }
+}
@@ -1,21 +1,87 @@
+use benimator::SpriteSheetAnimation;
 use bevy::prelude::*;
 
 mod animation_tree;
+mod aseprite;
+mod asset;
+mod blend_space;
+#[cfg(feature = "editor_window")]
+mod debug;
+mod events;
+mod layer;
+mod parameter;
+mod state;
+mod sub_machine;
+mod template;
+#[cfg(test)]
+mod tests;
+mod track;
+mod transition;
 
 pub use animation_tree::*;
+pub use aseprite::*;
+pub use asset::*;
+pub use blend_space::*;
+pub use events::*;
+pub use layer::*;
+pub use parameter::*;
+pub use state::*;
+pub use sub_machine::*;
+pub use template::*;
+pub use track::*;
+pub use transition::*;
 
 pub struct AnimationTreePlugin;
 
 impl Plugin for AnimationTreePlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_to_stage(CoreStage::PreUpdate, create_animation)
+        // The tree plays the frames itself, benimator only provides the animation assets.
+        app.add_asset::<SpriteSheetAnimation>()
+            .add_event::<AnimationMarker>()
+            .add_event::<AnimationStarted>()
+            .add_event::<AnimationFinished>()
+            .add_asset::<AsepriteSheet>()
+            .init_asset_loader::<AsepriteLoader>()
+            .register_type::<AnimationTree>()
+            .register_type::<AnimationParameter>()
+            .register_type::<AnimationTreeState>()
+            .add_asset::<AnimationTreeAsset>()
+            .init_resource::<AnimationTemplates>()
+            .init_resource::<AnimationTimestep>()
+            .init_asset_loader::<AnimationTreeLoader>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                instance_animation_tree.before("animation_create"),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                restore_animation_tree.before("animation_create"),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                create_animation.label("animation_create"),
+            )
+            .add_system_to_stage(
+                CoreStage::Update,
+                animation_parameters
+                    .label("animation_parameters")
+                    .before("animation_next"),
+            )
             .add_system_to_stage(CoreStage::Update, next_animation.label("animation_next"))
             .add_system_to_stage(
                 CoreStage::Update,
                 animation_tree
                     .label("animation_tree")
                     .after("animation_next"),
+            )
+            .add_system_to_stage(CoreStage::Update, animation_layer.after("animation_tree"))
+            .add_system_to_stage(
+                CoreStage::Update,
+                animation_track.exclusive_system().at_end(),
             );
+
+        #[cfg(feature = "editor_window")]
+        app.add_system(debug::animation_tree_window);
     }
 
     fn name(&self) -> &str {
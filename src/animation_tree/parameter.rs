@@ -0,0 +1,62 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::AnimationTree;
+
+/// A value owned by the tree, written by gameplay and read by the transitions.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect_value(PartialEq, Serialize, Deserialize)]
+pub enum AnimationParameter {
+    Float(f32),
+    Bool(bool),
+    /// A bool reset by the transition it fires.
+    Trigger(bool),
+    Vec2(Vec2),
+}
+
+/// Condition on a parameter of the tree, a transition fires by itself once all of its hold.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub enum ParameterCondition {
+    Greater(String, f32),
+    Less(String, f32),
+    True(String),
+    False(String),
+    Trigger(String),
+}
+
+impl ParameterCondition {
+    pub(crate) fn holds(&self, parameter: Option<&AnimationParameter>) -> bool {
+        match (self, parameter) {
+            (ParameterCondition::Greater(_, value), Some(AnimationParameter::Float(parameter))) => {
+                parameter > value
+            }
+            (ParameterCondition::Less(_, value), Some(AnimationParameter::Float(parameter))) => {
+                parameter < value
+            }
+            (ParameterCondition::True(_), Some(AnimationParameter::Bool(parameter))) => *parameter,
+            (ParameterCondition::False(_), Some(AnimationParameter::Bool(parameter))) => {
+                !*parameter
+            }
+            (ParameterCondition::Trigger(_), Some(AnimationParameter::Trigger(parameter))) => {
+                *parameter
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn parameter(&self) -> &str {
+        match self {
+            ParameterCondition::Greater(name, _)
+            | ParameterCondition::Less(name, _)
+            | ParameterCondition::True(name)
+            | ParameterCondition::False(name)
+            | ParameterCondition::Trigger(name) => name,
+        }
+    }
+}
+
+pub(crate) fn animation_parameters(mut query: Query<&mut AnimationTree>) {
+    for mut animation_tree in query.iter_mut() {
+        animation_tree.evaluate_parameters();
+    }
+}
@@ -0,0 +1,110 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use super::{AnimationParameter, AnimationTemplates, AnimationTree, AnimationTreeAsset};
+
+/// Playing state of an `AnimationTree`, for saves and scenes.
+///
+/// The tree itself shares its nodes with its template and can not be serialized. An entity
+/// spawned with this component, e.g. from a `DynamicScene`, gets the tree of `template` back
+/// and resumes on the same frame.
+#[derive(Component, Clone, Debug, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+pub struct AnimationTreeState {
+    /// Name of the tree in `AnimationTemplates`.
+    pub template: String,
+    pub current: String,
+    pub point: Vec2,
+    pub direction: Option<usize>,
+    pub frame: usize,
+    /// Seconds spent in the current frame.
+    pub elapsed: f32,
+    pub backward: bool,
+    pub stopped: bool,
+    pub parameters: HashMap<String, AnimationParameter>,
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+    /// Node traveled to and not entered yet, empty when none.
+    #[serde(default)]
+    pub next: String,
+    #[serde(default)]
+    pub pending: Vec<String>,
+    /// `next` waits for the current animation to finish.
+    #[serde(default)]
+    pub wait_end: bool,
+}
+
+fn default_speed() -> f32 {
+    1.
+}
+
+impl Default for AnimationTreeState {
+    fn default() -> Self {
+        AnimationTreeState {
+            template: String::new(),
+            current: String::new(),
+            point: Vec2::ZERO,
+            direction: None,
+            frame: 0,
+            elapsed: 0.,
+            backward: false,
+            stopped: false,
+            parameters: HashMap::new(),
+            speed: default_speed(),
+            next: String::new(),
+            pending: Vec::new(),
+            wait_end: false,
+        }
+    }
+}
+
+/// Save the state of every tree instanced from a template into its `AnimationTreeState`.
+///
+/// Run it right before exporting the world, e.g. into a `DynamicScene`.
+pub fn save_animation_trees(world: &mut World) {
+    let mut query = world.query::<(Entity, &AnimationTree, &Handle<AnimationTreeAsset>)>();
+    let templates = world.resource::<AnimationTemplates>();
+    let states: Vec<_> = query
+        .iter(world)
+        .filter_map(|(entity, animation_tree, handle)| {
+            let template = templates.name(handle)?.to_string();
+            Some((entity, animation_tree.save_state(template)))
+        })
+        .collect();
+
+    for (entity, state) in states {
+        world.entity_mut(entity).insert(state);
+    }
+}
+
+/// Instance the tree of loaded `AnimationTreeState`s once their template is ready.
+pub(crate) fn restore_animation_tree(
+    mut commands: Commands,
+    templates: Res<AnimationTemplates>,
+    assets: Res<Assets<AnimationTreeAsset>>,
+    query: Query<(Entity, &AnimationTreeState, Option<&AnimationTree>)>,
+) {
+    for (entity, state, animation_tree) in query.iter() {
+        // A live tree only keeps its last save, a tree loaded from a scene has no nodes.
+        if animation_tree.map_or(false, |animation_tree| !animation_tree.nodes().is_empty()) {
+            continue;
+        }
+        let handle = match templates.get(&state.template) {
+            Some(handle) => handle,
+            None => continue,
+        };
+        let mut tree = match assets.get(&handle) {
+            Some(asset) => asset.tree.clone(),
+            None => continue,
+        };
+        tree.restore_state(state);
+
+        let mut entity_commands = commands.entity(entity);
+        if let Some(animation) = tree.animation() {
+            entity_commands.insert(animation.handle().clone());
+        }
+        entity_commands
+            .insert_bundle((tree, handle))
+            .remove::<AnimationTreeState>();
+    }
+}
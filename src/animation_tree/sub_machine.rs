@@ -0,0 +1,119 @@
+use bevy::utils::HashMap;
+
+use super::{AnimationNodeMachine, AnimationTransition};
+
+/// Group of nodes played as a single node of the parent graph, e.g. the "flying" phase of a bat.
+///
+/// Its nodes are named `"{machine}/{node}"` in the tree. Names used by the transitions of a node
+/// are looked up in its own machine first, then in the enclosing ones, so a node can leave to the
+/// parent graph by naming one of its nodes. Naming a machine enters its `entry` node.
+#[derive(Clone)]
+pub struct AnimationSubMachine {
+    entry: String,
+    nodes: HashMap<String, AnimationNodeMachine>,
+    machines: HashMap<String, AnimationSubMachine>,
+    exit: Option<String>,
+    next: Option<String>,
+    transitions: Vec<AnimationTransition>,
+}
+
+impl AnimationSubMachine {
+    pub fn from(nodes: HashMap<String, AnimationNodeMachine>, entry: String) -> Self {
+        AnimationSubMachine {
+            entry,
+            nodes,
+            machines: HashMap::new(),
+            exit: None,
+            next: None,
+            transitions: Vec::new(),
+        }
+    }
+
+    /// Nest `machine` as the node `name` of this machine.
+    pub fn machine(mut self, name: String, machine: AnimationSubMachine) -> Self {
+        self.machines.insert(name, machine);
+        self
+    }
+
+    /// Leave the machine to its `next` node once the `exit` node finished.
+    pub fn exit(mut self, exit: String) -> Self {
+        self.exit = Some(exit);
+        self
+    }
+
+    pub fn next(mut self, next: String) -> Self {
+        self.next = Some(next);
+        self
+    }
+
+    /// Allow leaving the machine from any of its nodes through `transition`.
+    pub fn transition(mut self, transition: AnimationTransition) -> Self {
+        self.transitions.push(transition);
+        self
+    }
+
+    /// Full name of the node played when entering `name` of this machine.
+    fn entry_of(&self, prefix: &str, name: &str) -> Option<String> {
+        if self.nodes.contains_key(name) {
+            return Some(format!("{}{}", prefix, name));
+        }
+        let machine = self.machines.get(name)?;
+        machine.entry_of(&format!("{}{}/", prefix, name), &machine.entry)
+    }
+
+    /// Full name of the node played when entering this machine as the root of a tree.
+    pub(crate) fn root_entry(&self) -> String {
+        self.entry_of("", &self.entry)
+            .unwrap_or_else(|| self.entry.clone())
+    }
+
+    /// Insert the nodes of this machine and the nested ones into `nodes`, by their full names.
+    ///
+    /// `entries` receives the entry node of every nested machine, by full name of the machine.
+    pub(crate) fn flatten<'a>(
+        &'a self,
+        prefix: &str,
+        scopes: &mut Vec<(String, &'a AnimationSubMachine)>,
+        inherited: &[AnimationTransition],
+        nodes: &mut HashMap<String, AnimationNodeMachine>,
+        entries: &mut HashMap<String, String>,
+    ) {
+        let resolve = |scopes: &[(String, &AnimationSubMachine)], name: &str| {
+            scopes
+                .iter()
+                .rev()
+                .find_map(|(prefix, machine)| machine.entry_of(prefix, name))
+                .unwrap_or_else(|| name.to_string())
+        };
+
+        // The edges and the next node of the machine are named in the parent graph.
+        let mut leave: Vec<AnimationTransition> = self
+            .transitions
+            .iter()
+            .map(|transition| AnimationTransition {
+                to: resolve(scopes, &transition.to),
+                ..transition.clone()
+            })
+            .collect();
+        leave.extend(inherited.iter().cloned());
+        let next = self.next.as_ref().map(|next| resolve(scopes, next));
+
+        scopes.push((prefix.to_string(), self));
+        for (name, node) in self.nodes.iter() {
+            let mut node = node.scoped(|to| resolve(scopes, to), &leave);
+            if self.exit.as_ref() == Some(name) {
+                node = node.exit_to(next.clone());
+            }
+            nodes.insert(format!("{}{}", prefix, name), node);
+        }
+
+        for (name, machine) in self.machines.iter() {
+            let machine_prefix = format!("{}{}/", prefix, name);
+            if let Some(entry) = machine.entry_of(&machine_prefix, &machine.entry) {
+                entries.insert(format!("{}{}", prefix, name), entry);
+            }
+            machine.flatten(&machine_prefix, scopes, &leave, nodes, entries);
+        }
+        scopes.pop();
+    }
+}
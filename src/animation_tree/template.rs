@@ -0,0 +1,50 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{AnimationTree, AnimationTreeAsset};
+
+/// Animation trees built once and shared by name, e.g. by every bat of a level.
+///
+/// Inserting the handle of a template on an entity instances its `AnimationTree`, which shares
+/// the nodes and animations of the template.
+#[derive(Default)]
+pub struct AnimationTemplates {
+    templates: HashMap<String, Handle<AnimationTreeAsset>>,
+}
+
+impl AnimationTemplates {
+    /// Register the tree built in code as `name`.
+    pub fn insert(
+        &mut self,
+        assets: &mut Assets<AnimationTreeAsset>,
+        name: String,
+        tree: AnimationTree,
+    ) -> Handle<AnimationTreeAsset> {
+        let handle = assets.add(AnimationTreeAsset { tree });
+        self.templates.insert(name, handle.clone());
+        handle
+    }
+
+    /// Register the `.animtree.ron` file at `path` as `name`.
+    pub fn load(
+        &mut self,
+        asset_server: &AssetServer,
+        name: String,
+        path: &str,
+    ) -> Handle<AnimationTreeAsset> {
+        let handle = asset_server.load(path);
+        self.templates.insert(name, handle.clone());
+        handle
+    }
+
+    pub fn get(&self, name: &str) -> Option<Handle<AnimationTreeAsset>> {
+        self.templates.get(name).cloned()
+    }
+
+    /// Name the template `handle` is registered as.
+    pub fn name(&self, handle: &Handle<AnimationTreeAsset>) -> Option<&str> {
+        self.templates
+            .iter()
+            .find(|(_, template)| *template == handle)
+            .map(|(name, _)| name.as_str())
+    }
+}
@@ -0,0 +1,728 @@
+use std::{ops::RangeInclusive, time::Duration};
+
+use benimator::SpriteSheetAnimation;
+use bevy::{
+    asset::AssetPlugin,
+    ecs::event::{Events, ManualEventReader},
+    prelude::*,
+    utils::HashMap,
+};
+
+use super::*;
+
+const FRAME: Duration = Duration::from_millis(100);
+
+/// Headless app stepping the animations by exactly one frame per update.
+struct TestApp {
+    app: App,
+    started: ManualEventReader<AnimationStarted>,
+    finished: ManualEventReader<AnimationFinished>,
+    /// `"started {node}"` and `"finished {node}"`, in the order they were sent.
+    events: Vec<String>,
+}
+
+impl TestApp {
+    fn new() -> Self {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_plugin(AnimationTreePlugin)
+            .insert_resource(AnimationTimestep(Some(FRAME)));
+
+        TestApp {
+            app,
+            started: ManualEventReader::default(),
+            finished: ManualEventReader::default(),
+            events: Vec::new(),
+        }
+    }
+
+    fn animation(&mut self, frames: RangeInclusive<usize>) -> Animation {
+        let animation = SpriteSheetAnimation::from_range(frames, FRAME);
+        Animation::from(
+            self.app
+                .world
+                .resource_mut::<Assets<SpriteSheetAnimation>>()
+                .add(animation),
+        )
+    }
+
+    fn node(&mut self, frames: RangeInclusive<usize>) -> AnimationNodeMachine {
+        let animation = self.animation(frames);
+        AnimationNodeMachine::from(vec![AnimationNode::from(animation, Vec2::ZERO)])
+    }
+
+    fn spawn(&mut self, tree: AnimationTree) -> Entity {
+        self.app
+            .world
+            .spawn()
+            .insert_bundle((tree, TextureAtlasSprite::default()))
+            .id()
+    }
+
+    fn update(&mut self) {
+        self.app.update();
+
+        let started = self.app.world.resource::<Events<AnimationStarted>>();
+        for event in self.started.iter(started) {
+            self.events.push(format!("started {}", event.node));
+        }
+        let finished = self.app.world.resource::<Events<AnimationFinished>>();
+        for event in self.finished.iter(finished) {
+            self.events.push(format!("finished {}", event.node));
+        }
+    }
+
+    fn update_times(&mut self, times: usize) {
+        for _ in 0..times {
+            self.update();
+        }
+    }
+
+    fn tree(&mut self, entity: Entity) -> Mut<AnimationTree> {
+        self.app.world.get_mut::<AnimationTree>(entity).unwrap()
+    }
+
+    fn current(&self, entity: Entity) -> String {
+        let tree = self.app.world.get::<AnimationTree>(entity).unwrap();
+        tree.current().to_string()
+    }
+
+    fn sprite_index(&self, entity: Entity) -> usize {
+        self.app
+            .world
+            .get::<TextureAtlasSprite>(entity)
+            .unwrap()
+            .index
+    }
+
+    /// Take the events sent since the last call.
+    fn events(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+/// `idle` loops, `attack` plays once then goes back to `idle`, `stun` plays once and stops.
+fn character(test: &mut TestApp) -> Entity {
+    let mut nodes = HashMap::new();
+    nodes.insert("idle".to_string(), test.node(0..=1));
+    nodes.insert(
+        "attack".to_string(),
+        test.node(10..=13)
+            .mode(AnimationMode::Once)
+            .next("idle".to_string()),
+    );
+    nodes.insert(
+        "stun".to_string(),
+        test.node(20..=21).mode(AnimationMode::Once),
+    );
+    test.spawn(AnimationTree::from(nodes, "idle".to_string()))
+}
+
+#[test]
+fn starts_on_the_default_node() {
+    let mut test = TestApp::new();
+    let entity = character(&mut test);
+
+    test.update();
+
+    assert_eq!(test.current(entity), "idle");
+    assert_eq!(test.events(), ["started idle"]);
+    assert!(test
+        .app
+        .world
+        .get::<Handle<SpriteSheetAnimation>>(entity)
+        .is_some());
+}
+
+#[test]
+fn loops_without_leaving_the_node() {
+    let mut test = TestApp::new();
+    let entity = character(&mut test);
+
+    test.update();
+    let mut indices = Vec::new();
+    for _ in 0..4 {
+        test.update();
+        indices.push(test.sprite_index(entity));
+    }
+
+    // Nothing traveled, the empty `next` must not replace the current node.
+    assert_eq!(test.current(entity), "idle");
+    assert_eq!(indices, [0, 1, 0, 1]);
+    assert_eq!(test.events(), ["started idle"]);
+}
+
+#[test]
+fn leaves_the_flip_of_other_systems_alone() {
+    let mut test = TestApp::new();
+    let entity = character(&mut test);
+    test.update();
+
+    let mut sprite = test
+        .app
+        .world
+        .get_mut::<TextureAtlasSprite>(entity)
+        .unwrap();
+    sprite.flip_x = true;
+    test.update();
+
+    let sprite = test.app.world.get::<TextureAtlasSprite>(entity).unwrap();
+    assert!(sprite.flip_x);
+}
+
+#[test]
+fn invalid_speeds_do_not_panic() {
+    let mut test = TestApp::new();
+    let entity = character(&mut test);
+    test.update();
+
+    for speed in [f32::INFINITY, f32::NAN, -1.] {
+        test.tree(entity).speed = speed;
+        test.update();
+    }
+    assert_eq!(test.current(entity), "idle");
+}
+
+#[test]
+fn travel_enters_the_node() {
+    let mut test = TestApp::new();
+    let entity = character(&mut test);
+    test.update();
+    test.events();
+
+    assert!(test.tree(entity).travel(Vec2::ZERO, "attack".to_string()));
+    test.update();
+
+    assert_eq!(test.current(entity), "attack");
+    assert_eq!(test.events(), ["started attack"]);
+}
+
+#[test]
+fn travel_to_an_unknown_node_is_rejected() {
+    let mut test = TestApp::new();
+    let entity = character(&mut test);
+    test.update();
+
+    assert!(!test.tree(entity).travel(Vec2::ZERO, "fly".to_string()));
+    test.update();
+
+    assert_eq!(test.current(entity), "idle");
+}
+
+#[test]
+fn once_node_reports_finished_and_advances_to_next() {
+    let mut test = TestApp::new();
+    let entity = character(&mut test);
+    test.update();
+    test.events();
+
+    test.tree(entity).travel(Vec2::ZERO, "attack".to_string());
+    // Entered on the first update, then one frame per update.
+    test.update_times(3);
+    assert_eq!(test.current(entity), "attack");
+    assert_eq!(test.sprite_index(entity), 13);
+    assert_eq!(test.events(), ["started attack"]);
+
+    test.update();
+    assert_eq!(test.current(entity), "idle");
+    assert_eq!(test.events(), ["finished attack", "started idle"]);
+}
+
+#[test]
+fn once_node_without_next_stops_on_its_last_frame() {
+    let mut test = TestApp::new();
+    let entity = character(&mut test);
+    test.update();
+    test.events();
+
+    test.tree(entity).travel(Vec2::ZERO, "stun".to_string());
+    test.update_times(6);
+
+    assert_eq!(test.current(entity), "stun");
+    assert_eq!(test.sprite_index(entity), 21);
+    assert_eq!(test.events(), ["started stun", "finished stun"]);
+}
+
+#[test]
+fn from_frame_transition_waits_for_its_frame() {
+    let mut test = TestApp::new();
+    let mut nodes = HashMap::new();
+    nodes.insert(
+        "attack".to_string(),
+        test.node(0..=3).transition(AnimationTransition {
+            to: "roll".to_string(),
+            condition: TransitionCondition::FromFrame(2),
+            when: Vec::new(),
+        }),
+    );
+    nodes.insert("roll".to_string(), test.node(10..=13));
+    let entity = test.spawn(AnimationTree::from(nodes, "attack".to_string()));
+
+    // On frame 1.
+    test.update();
+    assert!(!test.tree(entity).travel(Vec2::ZERO, "roll".to_string()));
+
+    // On frame 2.
+    test.update();
+    assert!(test.tree(entity).travel(Vec2::ZERO, "roll".to_string()));
+    test.update();
+    assert_eq!(test.current(entity), "roll");
+}
+
+#[test]
+fn durations_hold_frames_and_skip_the_zero_ones() {
+    let mut test = TestApp::new();
+    let animation = test.animation(0..=3);
+    let point = AnimationNode::from(animation, Vec2::ZERO).durations(vec![
+        FRAME * 2,
+        Duration::ZERO,
+        FRAME,
+        FRAME,
+    ]);
+    let mut nodes = HashMap::new();
+    nodes.insert(
+        "attack".to_string(),
+        AnimationNodeMachine::from(vec![point]).mode(AnimationMode::Once),
+    );
+    let entity = test.spawn(AnimationTree::from(nodes, "attack".to_string()));
+
+    let mut indices = Vec::new();
+    for _ in 0..3 {
+        test.update();
+        indices.push(test.sprite_index(entity));
+    }
+    assert_eq!(indices, [0, 2, 3]);
+    assert_eq!(test.events(), ["started attack"]);
+
+    test.update();
+    assert_eq!(test.events(), ["finished attack"]);
+}
+
+#[test]
+fn at_end_transition_waits_for_the_finished_animation() {
+    let mut test = TestApp::new();
+    let mut nodes = HashMap::new();
+    nodes.insert(
+        "roll".to_string(),
+        test.node(0..=2)
+            .mode(AnimationMode::Once)
+            .transition(AnimationTransition {
+                to: "idle".to_string(),
+                condition: TransitionCondition::AtEnd,
+                when: Vec::new(),
+            }),
+    );
+    nodes.insert("idle".to_string(), test.node(10..=10));
+    let entity = test.spawn(AnimationTree::from(nodes, "roll".to_string()));
+
+    test.update();
+    assert!(test.tree(entity).travel(Vec2::ZERO, "idle".to_string()));
+    test.update();
+    assert_eq!(test.current(entity), "roll");
+
+    test.update();
+    assert_eq!(test.current(entity), "idle");
+    assert_eq!(
+        test.events(),
+        ["started roll", "finished roll", "started idle"]
+    );
+}
+
+#[test]
+fn at_end_transition_from_a_looping_node_is_rejected() {
+    let mut test = TestApp::new();
+    let mut nodes = HashMap::new();
+    nodes.insert(
+        "idle".to_string(),
+        test.node(0..=1).transition(AnimationTransition {
+            to: "sleep".to_string(),
+            condition: TransitionCondition::AtEnd,
+            when: Vec::new(),
+        }),
+    );
+    nodes.insert("sleep".to_string(), test.node(10..=11));
+    let entity = test.spawn(AnimationTree::from(nodes, "idle".to_string()));
+    test.update();
+
+    assert!(!test.tree(entity).travel(Vec2::ZERO, "sleep".to_string()));
+    test.update_times(4);
+    assert_eq!(test.current(entity), "idle");
+}
+
+#[test]
+fn at_end_transition_from_a_stopped_node_leaves_right_away() {
+    let mut test = TestApp::new();
+    let mut nodes = HashMap::new();
+    nodes.insert(
+        "stun".to_string(),
+        test.node(20..=21)
+            .mode(AnimationMode::Once)
+            .transition(AnimationTransition {
+                to: "idle".to_string(),
+                condition: TransitionCondition::AtEnd,
+                when: Vec::new(),
+            }),
+    );
+    nodes.insert("idle".to_string(), test.node(0..=1));
+    let entity = test.spawn(AnimationTree::from(nodes, "stun".to_string()));
+
+    test.update_times(3);
+    assert_eq!(test.events(), ["started stun", "finished stun"]);
+
+    assert!(test.tree(entity).travel(Vec2::ZERO, "idle".to_string()));
+    test.update();
+    assert_eq!(test.current(entity), "idle");
+    assert_eq!(test.events(), ["started idle"]);
+}
+
+#[test]
+fn keep_time_direction_switch_keeps_the_phase() {
+    let mut test = TestApp::new();
+    let right = test.animation(0..=3);
+    let left = test.animation(10..=17);
+    let mut nodes = HashMap::new();
+    nodes.insert(
+        "run".to_string(),
+        AnimationNodeMachine::from(vec![
+            AnimationNode::from(right, Vec2::X),
+            AnimationNode::from(left, -Vec2::X),
+        ])
+        .direction_switch(DirectionSwitch::KeepTime),
+    );
+    let entity = test.spawn(AnimationTree::from(nodes, "run".to_string()));
+
+    // Halfway through the 4 frames going right.
+    test.update_times(2);
+    assert_eq!(test.sprite_index(entity), 2);
+
+    test.tree(entity).travel(-Vec2::X, "run".to_string());
+    test.update();
+
+    // Halfway through the 8 frames going left, then one more frame played.
+    assert_eq!(test.tree(entity).direction(), Some(1));
+    assert_eq!(test.tree(entity).frame(), 5);
+    assert_eq!(test.sprite_index(entity), 15);
+    assert_eq!(test.events(), ["started run"]);
+}
+
+#[test]
+fn random_start_does_not_apply_to_a_direction_switch() {
+    let mut test = TestApp::new();
+    let right = test.animation(0..=3);
+    let left = test.animation(10..=13);
+    let mut nodes = HashMap::new();
+    nodes.insert(
+        "idle".to_string(),
+        AnimationNodeMachine::from(vec![
+            AnimationNode::from(right, Vec2::X),
+            AnimationNode::from(left, -Vec2::X),
+        ])
+        .random_start(),
+    );
+    let entity = test.spawn(AnimationTree::from(nodes, "idle".to_string()));
+    test.update();
+
+    test.tree(entity).travel(-Vec2::X, "idle".to_string());
+    test.update();
+
+    // Restarted on the first frame, then one frame played.
+    assert_eq!(test.tree(entity).frame(), 1);
+    assert_eq!(test.sprite_index(entity), 11);
+}
+
+#[test]
+fn parameters_fire_transitions_and_consume_triggers() {
+    let mut test = TestApp::new();
+    let mut nodes = HashMap::new();
+    nodes.insert(
+        "idle".to_string(),
+        test.node(0..=1)
+            .transition(AnimationTransition {
+                to: "run".to_string(),
+                condition: TransitionCondition::Immediate,
+                when: vec![ParameterCondition::True("is_moving".to_string())],
+            })
+            .transition(AnimationTransition {
+                to: "attack".to_string(),
+                condition: TransitionCondition::Immediate,
+                when: vec![ParameterCondition::Trigger("attack".to_string())],
+            }),
+    );
+    nodes.insert("run".to_string(), test.node(2..=3));
+    nodes.insert(
+        "attack".to_string(),
+        test.node(4..=5)
+            .mode(AnimationMode::Once)
+            .next("idle".to_string()),
+    );
+    let tree = AnimationTree::from(nodes, "idle".to_string())
+        .parameter("is_moving".to_string(), AnimationParameter::Bool(false))
+        .parameter("attack".to_string(), AnimationParameter::Trigger(false));
+    let entity = test.spawn(tree);
+    test.update();
+
+    test.tree(entity).set_trigger("attack");
+    test.update();
+    assert_eq!(test.current(entity), "attack");
+    assert_eq!(
+        test.tree(entity).get_parameter("attack"),
+        Some(&AnimationParameter::Trigger(false))
+    );
+
+    // Back to idle, the consumed trigger does not fire again.
+    test.update_times(2);
+    assert_eq!(test.current(entity), "idle");
+
+    test.tree(entity).set_bool("is_moving", true);
+    test.update();
+    assert_eq!(test.current(entity), "run");
+}
+
+#[test]
+fn travel_path_plays_the_intermediate_nodes() {
+    let mut test = TestApp::new();
+    let mut nodes = HashMap::new();
+    nodes.insert(
+        "idle".to_string(),
+        test.node(0..=0).transition(AnimationTransition {
+            to: "crouch".to_string(),
+            condition: TransitionCondition::Immediate,
+            when: Vec::new(),
+        }),
+    );
+    nodes.insert(
+        "crouch".to_string(),
+        test.node(1..=2)
+            .mode(AnimationMode::Once)
+            .transition(AnimationTransition {
+                to: "crawl".to_string(),
+                condition: TransitionCondition::AtEnd,
+                when: Vec::new(),
+            }),
+    );
+    nodes.insert("crawl".to_string(), test.node(3..=4));
+    let entity = test.spawn(AnimationTree::from(nodes, "idle".to_string()));
+    test.update();
+    test.events();
+
+    assert!(test
+        .tree(entity)
+        .travel_path(Vec2::ZERO, "crawl".to_string()));
+    test.update_times(3);
+
+    assert_eq!(test.current(entity), "crawl");
+    assert_eq!(
+        test.events(),
+        ["started crouch", "finished crouch", "started crawl"]
+    );
+}
+
+#[test]
+fn traveling_to_a_sub_machine_enters_its_entry_node() {
+    let mut test = TestApp::new();
+    let mut flying = HashMap::new();
+    flying.insert("fly".to_string(), test.node(0..=1));
+    flying.insert("dive".to_string(), test.node(2..=3));
+    let mut nodes = HashMap::new();
+    nodes.insert("stunned".to_string(), test.node(4..=5));
+    let root = AnimationSubMachine::from(nodes, "flying".to_string()).machine(
+        "flying".to_string(),
+        AnimationSubMachine::from(flying, "fly".to_string()),
+    );
+    let entity = test.spawn(AnimationTree::from_machine(root));
+
+    test.update();
+    assert_eq!(test.current(entity), "flying/fly");
+    assert!(test.tree(entity).is_in("flying"));
+
+    test.tree(entity).travel(Vec2::ZERO, "stunned".to_string());
+    test.update();
+    assert_eq!(test.current(entity), "stunned");
+
+    test.tree(entity).travel(Vec2::ZERO, "flying".to_string());
+    test.update();
+    assert_eq!(test.current(entity), "flying/fly");
+}
+
+#[test]
+fn saved_state_resumes_on_the_same_frame() {
+    let mut test = TestApp::new();
+    let mut nodes = HashMap::new();
+    nodes.insert("idle".to_string(), test.node(0..=1));
+    nodes.insert("attack".to_string(), test.node(10..=13));
+    let tree = AnimationTree::from(nodes, "idle".to_string());
+    let template =
+        test.app
+            .world
+            .resource_scope(|world, mut templates: Mut<AnimationTemplates>| {
+                let mut assets = world.resource_mut::<Assets<AnimationTreeAsset>>();
+                templates.insert(&mut assets, "character".to_string(), tree)
+            });
+    let entity = test
+        .app
+        .world
+        .spawn()
+        .insert_bundle((template, TextureAtlasSprite::default()))
+        .id();
+    test.update_times(2);
+    test.tree(entity).travel(Vec2::ZERO, "attack".to_string());
+    test.tree(entity).speed = 0.5;
+    test.update_times(2);
+
+    save_animation_trees(&mut test.app.world);
+    let state = test
+        .app
+        .world
+        .get::<AnimationTreeState>(entity)
+        .unwrap()
+        .clone();
+    assert_eq!(state.template, "character");
+    let loaded = test
+        .app
+        .world
+        .spawn()
+        .insert_bundle((state, TextureAtlasSprite::default()))
+        .id();
+    test.update();
+
+    assert_eq!(test.current(loaded), "attack");
+    assert_eq!(test.sprite_index(loaded), test.sprite_index(entity));
+    assert_eq!(test.tree(loaded).speed, 0.5);
+    assert!(test.app.world.get::<AnimationTreeState>(loaded).is_none());
+}
+
+#[test]
+fn reverse_and_ping_pong_playback_order() {
+    let mut test = TestApp::new();
+    let mut nodes = HashMap::new();
+    nodes.insert(
+        "breathe".to_string(),
+        test.node(0..=2).playback(AnimationPlayback::PingPong),
+    );
+    nodes.insert(
+        "rewind".to_string(),
+        test.node(10..=12)
+            .playback(AnimationPlayback::Reverse)
+            .mode(AnimationMode::Once),
+    );
+    let entity = test.spawn(AnimationTree::from(nodes, "breathe".to_string()));
+
+    let mut indices = Vec::new();
+    for _ in 0..6 {
+        test.update();
+        indices.push(test.sprite_index(entity));
+    }
+    assert_eq!(indices, [1, 2, 1, 0, 1, 2]);
+
+    test.tree(entity).travel(Vec2::ZERO, "rewind".to_string());
+    let mut indices = Vec::new();
+    for _ in 0..3 {
+        test.update();
+        indices.push(test.sprite_index(entity));
+    }
+    assert_eq!(indices, [11, 10, 10]);
+    assert_eq!(test.events().last().unwrap(), "finished rewind");
+}
+
+#[test]
+fn overlay_layer_plays_its_own_node_over_the_tree() {
+    let mut test = TestApp::new();
+    let mut nodes = HashMap::new();
+    nodes.insert("run".to_string(), test.node(0..=3));
+    let legs = test.animation(20..=23);
+    let attack = test.animation(30..=31);
+    let layer = AnimationLayer::default()
+        .node("run".to_string(), vec![legs])
+        .node("attack".to_string(), vec![attack]);
+    let entity =
+        test.spawn(AnimationTree::from(nodes, "run".to_string()).layer("upper".to_string(), layer));
+    let child = test
+        .app
+        .world
+        .spawn()
+        .insert_bundle((
+            AnimationLayerSprite::new("upper".to_string()),
+            TextureAtlasSprite::default(),
+            Visibility::default(),
+        ))
+        .id();
+    test.app.world.entity_mut(entity).push_children(&[child]);
+    test.update();
+    assert_eq!(test.sprite_index(child), 20 + test.tree(entity).frame());
+
+    test.app
+        .world
+        .get_mut::<AnimationLayerSprite>(child)
+        .unwrap()
+        .play("attack".to_string());
+    let mut indices = Vec::new();
+    for _ in 0..2 {
+        test.update();
+        indices.push(test.sprite_index(child));
+    }
+    assert_eq!(indices, [30, 31]);
+    assert_eq!(test.current(entity), "run");
+
+    test.update();
+    let layer_sprite = test.app.world.get::<AnimationLayerSprite>(child).unwrap();
+    assert_eq!(layer_sprite.playing(), None);
+    assert_eq!(test.sprite_index(child), 20 + test.tree(entity).frame());
+    assert_eq!(test.sprite_index(entity), test.tree(entity).frame());
+}
+
+/// Up, right, down and left.
+const CARDINAL: [Vec2; 4] = [
+    Vec2::new(0., 1.),
+    Vec2::new(1., 0.),
+    Vec2::new(0., -1.),
+    Vec2::new(-1., 0.),
+];
+
+fn blend_space(tie_break: TieBreak) -> BlendSpace {
+    BlendSpace {
+        hysteresis: 0.,
+        tie_break,
+    }
+}
+
+#[test]
+fn blend_space_tie_breaks() {
+    let up_right = Vec2::new(1., 1.).normalize();
+    let down_right = Vec2::new(1., -1.).normalize();
+
+    let first = blend_space(TieBreak::First);
+    assert_eq!(first.pick(&CARDINAL, up_right, None), Some(0));
+    assert_eq!(first.pick(&CARDINAL, down_right, None), Some(1));
+
+    let horizontal = blend_space(TieBreak::Horizontal);
+    assert_eq!(horizontal.pick(&CARDINAL, up_right, None), Some(1));
+    assert_eq!(horizontal.pick(&CARDINAL, down_right, None), Some(1));
+
+    let vertical = blend_space(TieBreak::Vertical);
+    assert_eq!(vertical.pick(&CARDINAL, up_right, None), Some(0));
+    assert_eq!(vertical.pick(&CARDINAL, down_right, None), Some(2));
+
+    let current = blend_space(TieBreak::Current);
+    assert_eq!(current.pick(&CARDINAL, up_right, Some(1)), Some(1));
+    assert_eq!(current.pick(&CARDINAL, up_right, Some(0)), Some(0));
+    // Not one of the ties, the first tie wins.
+    assert_eq!(current.pick(&CARDINAL, up_right, Some(2)), Some(0));
+    assert_eq!(current.pick(&CARDINAL, up_right, None), Some(0));
+}
+
+#[test]
+fn blend_space_hysteresis_keeps_the_current_point() {
+    let blend_space = BlendSpace {
+        hysteresis: 0.2,
+        tie_break: TieBreak::First,
+    };
+    // Closer to up, by less than the hysteresis.
+    let slightly_up = Vec2::new(0.65, 0.76);
+    assert_eq!(blend_space.pick(&CARDINAL, slightly_up, Some(1)), Some(1));
+    assert_eq!(blend_space.pick(&CARDINAL, slightly_up, None), Some(0));
+
+    // Closer by more than the hysteresis.
+    let mostly_up = Vec2::new(0.5, 0.87);
+    assert_eq!(blend_space.pick(&CARDINAL, mostly_up, Some(1)), Some(0));
+}
@@ -0,0 +1,168 @@
+use benimator::SpriteSheetAnimation;
+use bevy::{
+    prelude::*,
+    reflect::{GetPath, TypeRegistryArc},
+};
+use serde::Deserialize;
+
+use super::AnimationTree;
+
+/// Value written by a track, numeric values are interpolated between keyframes.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum TrackValue {
+    Bool(bool),
+    Float(f32),
+    Vec2(Vec2),
+    Vec3(Vec3),
+    Color(Color),
+}
+
+impl TrackValue {
+    fn lerp(&self, other: &TrackValue, t: f32) -> TrackValue {
+        match (self, other) {
+            (TrackValue::Float(a), TrackValue::Float(b)) => TrackValue::Float(a + (b - a) * t),
+            (TrackValue::Vec2(a), TrackValue::Vec2(b)) => TrackValue::Vec2(a.lerp(*b, t)),
+            (TrackValue::Vec3(a), TrackValue::Vec3(b)) => TrackValue::Vec3(a.lerp(*b, t)),
+            (TrackValue::Color(a), TrackValue::Color(b)) => {
+                TrackValue::Color(Vec4::from(*a).lerp(Vec4::from(*b), t).into())
+            }
+            // Discrete values hold until the next keyframe.
+            _ => *self,
+        }
+    }
+
+    /// Whether `field` holds a value of the same type, different from this one.
+    fn differs(&self, field: &dyn Reflect) -> bool {
+        fn differs<T: Reflect + PartialEq>(field: &dyn Reflect, value: &T) -> bool {
+            field
+                .downcast_ref::<T>()
+                .map_or(false, |field| field != value)
+        }
+
+        match self {
+            TrackValue::Bool(value) => differs(field, value),
+            TrackValue::Float(value) => differs(field, value),
+            TrackValue::Vec2(value) => differs(field, value),
+            TrackValue::Vec3(value) => differs(field, value),
+            TrackValue::Color(value) => differs(field, value),
+        }
+    }
+
+    fn apply(&self, field: &mut dyn Reflect) {
+        fn apply<T: Reflect + Copy>(field: &mut dyn Reflect, value: &T) {
+            if let Some(field) = field.downcast_mut::<T>() {
+                *field = *value;
+            }
+        }
+
+        match self {
+            TrackValue::Bool(value) => apply(field, value),
+            TrackValue::Float(value) => apply(field, value),
+            TrackValue::Vec2(value) => apply(field, value),
+            TrackValue::Vec3(value) => apply(field, value),
+            TrackValue::Color(value) => apply(field, value),
+        }
+    }
+}
+
+/// Keyframes driving a field of a component while a node plays, like a Godot property track.
+///
+/// The component has to be registered with `#[reflect(Component)]`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AnimationTrack {
+    /// `Name` of the descendant entity to animate, the entity of the tree when `None`.
+    #[serde(default)]
+    pub target: Option<String>,
+    /// Short type name of the component, e.g. `Transform`.
+    pub component: String,
+    /// Reflect path of the field in the component, e.g. `translation.y`.
+    pub path: String,
+    /// Values by frame of the node animation.
+    pub keyframes: Vec<(usize, TrackValue)>,
+}
+
+impl AnimationTrack {
+    /// Value at `position`, counted in frames of the node animation.
+    pub fn sample(&self, position: f32) -> Option<TrackValue> {
+        let next = self
+            .keyframes
+            .iter()
+            .position(|(frame, _)| *frame as f32 > position);
+        match next {
+            Some(0) => self.keyframes.first().map(|(_, value)| *value),
+            Some(next) => {
+                let (from_frame, from) = self.keyframes[next - 1];
+                let (to_frame, to) = self.keyframes[next];
+                let t = (position - from_frame as f32) / (to_frame - from_frame) as f32;
+                Some(from.lerp(&to, t))
+            }
+            None => self.keyframes.last().map(|(_, value)| *value),
+        }
+    }
+}
+
+fn find_descendant(world: &World, entity: Entity, name: &str) -> Option<Entity> {
+    world.get::<Children>(entity)?.iter().find_map(|child| {
+        if world
+            .get::<Name>(*child)
+            .map_or(false, |n| n.as_str() == name)
+        {
+            Some(*child)
+        } else {
+            find_descendant(world, *child, name)
+        }
+    })
+}
+
+/// Write the tracks of the playing nodes into their components.
+pub(crate) fn animation_track(world: &mut World) {
+    let mut writes = Vec::new();
+    {
+        let mut query = world.query::<(Entity, &AnimationTree, &Handle<SpriteSheetAnimation>)>();
+        let animations = world.resource::<Assets<SpriteSheetAnimation>>();
+        for (entity, animation_tree, animation) in query.iter(world) {
+            let node = match animation_tree.nodes().get(animation_tree.current()) {
+                Some(node) if !node.tracks().is_empty() => node,
+                _ => continue,
+            };
+            let position = animations
+                .get(animation)
+                .map_or(0., |animation| animation_tree.position(animation));
+
+            for track in node.tracks() {
+                let target = match &track.target {
+                    Some(name) => find_descendant(world, entity, name),
+                    None => Some(entity),
+                };
+                if let (Some(target), Some(value)) = (target, track.sample(position)) {
+                    writes.push((target, track.component.clone(), track.path.clone(), value));
+                }
+            }
+        }
+    }
+
+    let registry = world.resource::<TypeRegistryArc>().clone();
+    let registry = registry.read();
+    for (entity, component, path, value) in writes {
+        let reflect_component = match registry
+            .get_with_short_name(&component)
+            .and_then(|registration| registration.data::<ReflectComponent>())
+        {
+            Some(reflect_component) => reflect_component,
+            None => continue,
+        };
+
+        // Only touch the component when the value changes, to keep change detection meaningful.
+        let differs = reflect_component
+            .reflect_component(world, entity)
+            .and_then(|component| component.path(&path).ok())
+            .map_or(false, |field| value.differs(field));
+        if differs {
+            if let Some(mut component) = reflect_component.reflect_component_mut(world, entity) {
+                if let Ok(field) = component.path_mut(&path) {
+                    value.apply(field);
+                }
+            }
+        }
+    }
+}
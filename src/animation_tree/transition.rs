@@ -0,0 +1,26 @@
+use serde::Deserialize;
+
+use super::ParameterCondition;
+
+/// When a transition is allowed to leave the current node.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+pub enum TransitionCondition {
+    /// Leave the current node right away.
+    #[default]
+    Immediate,
+    /// Leave once the current animation reached this frame.
+    FromFrame(usize),
+    /// Wait until the current non-looping animation finished.
+    AtEnd,
+}
+
+/// An allowed edge from a node of the tree to the node `to`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AnimationTransition {
+    pub to: String,
+    #[serde(default)]
+    pub condition: TransitionCondition,
+    /// Travel by itself once all of these hold, only by `AnimationTree::travel` when empty.
+    #[serde(default)]
+    pub when: Vec<ParameterCondition>,
+}
@@ -12,10 +12,19 @@ impl Plugin for CharacterPlugin {
         app.add_plugin(ActionPlugin::<player::Action>::default())
             .add_startup_system(player::setup)
             .add_system(player::spawn_player)
-            .add_system_to_stage(CoreStage::Update, player::movement.before("animation_next"))
-            .add_system_to_stage(CoreStage::Update, player::attack.before("animation_next"))
-            .add_system_to_stage(CoreStage::Update, player::roll.before("animation_next"))
-            .add_system_to_stage(CoreStage::Update, player::state.after("animation_tree"))
+            .add_system_to_stage(
+                CoreStage::Update,
+                player::movement.before("animation_parameters"),
+            )
+            .add_system_to_stage(
+                CoreStage::Update,
+                player::attack.before("animation_parameters"),
+            )
+            .add_system_to_stage(
+                CoreStage::Update,
+                player::roll.before("animation_parameters"),
+            )
+            .add_system(player::state)
             .register_ldtk_entity::<player::PlayerBundle>("Player")
             .register_type::<player::Player>()
             .register_type::<player::PlayerState>()
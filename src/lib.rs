@@ -0,0 +1,3 @@
+//! Plugins that do not depend on the game, shared with the benchmarks.
+
+pub mod ysort;
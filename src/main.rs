@@ -3,16 +3,14 @@ mod character;
 mod common;
 mod components;
 mod world;
-mod ysort;
 
 use animation_tree::AnimationTreePlugin;
-use benimator::AnimationPlugin;
 use bevy::{prelude::*, winit::WinitSettings};
+use bevy_learning::ysort::{YSortPlugin, YSortSpace};
 use bevy_rapier2d::prelude::*;
 use character::CharacterPlugin;
 use components::InputVector;
 use world::WorldPlugin;
-use ysort::YSortPlugin;
 
 fn main() {
     let mut app = App::new();
@@ -35,8 +33,9 @@ fn main() {
     }
 
     app.add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
+        // LDtk entities are children of their level.
+        .insert_resource(YSortSpace::Global)
         .add_plugin(YSortPlugin)
-        .add_plugin(AnimationPlugin::default())
         .add_plugin(AnimationTreePlugin)
         .add_plugin(GamePlugin)
         .add_plugin(WorldPlugin)
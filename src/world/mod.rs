@@ -2,16 +2,32 @@ use bevy::prelude::{App, Plugin};
 use bevy_ecs_ldtk::{prelude::RegisterLdtkObjects, LdtkPlugin, LevelSelection};
 
 mod map;
+mod prop;
+mod ysort;
 
 pub(crate) struct WorldPlugin;
 
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
+        let mut ldtk_ysort = ysort::LdtkYSort::default();
+        ldtk_ysort
+            .layer("AutoCliffTiles", 4)
+            .entity("Tree", 4)
+            .entity("Bush", 4);
+
         app.add_plugin(LdtkPlugin)
             .insert_resource(LevelSelection::Index(0))
+            .insert_resource(ldtk_ysort)
+            .init_resource::<ysort::LdtkYSortTiles>()
             .add_startup_system(map::setup)
             .add_system(map::spawn_wall_collision)
             .add_system(map::camera_fit_inside_current_level)
-            .register_ldtk_int_cell_for_layer::<map::WallBundle>("AutoCliffTiles", 1);
+            .add_system(ysort::hide_ysort_layers)
+            .add_system(ysort::collect_ysort_tiles)
+            .add_system(ysort::spawn_ysort_tiles)
+            .add_system(ysort::ysort_entities)
+            .register_ldtk_int_cell_for_layer::<map::WallBundle>("AutoCliffTiles", 1)
+            .register_ldtk_entity::<prop::PropBundle>("Tree")
+            .register_ldtk_entity::<prop::PropBundle>("Bush");
     }
 }
@@ -0,0 +1,33 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+/// Static object of the map, drawn with `World/<identifier>.png`.
+#[derive(Bundle)]
+pub(crate) struct PropBundle {
+    name: Name,
+    /// Read by `ysort_entities` to sort it with the characters.
+    entity_instance: EntityInstance,
+    #[bundle]
+    sprite: SpriteBundle,
+}
+
+impl LdtkEntity for PropBundle {
+    fn bundle_entity(
+        entity_instance: &EntityInstance,
+        _layer_instance: &LayerInstance,
+        _tileset: Option<&Handle<Image>>,
+        _tileset_definition: Option<&TilesetDefinition>,
+        asset_server: &AssetServer,
+        _texture_atlases: &mut Assets<TextureAtlas>,
+    ) -> Self {
+        PropBundle {
+            name: Name::from(entity_instance.identifier.as_str()),
+            entity_instance: entity_instance.clone(),
+            // Placed by bevy_ecs_ldtk from the pivot of the entity.
+            sprite: SpriteBundle {
+                texture: asset_server.load(&format!("World/{}.png", entity_instance.identifier)),
+                ..default()
+            },
+        }
+    }
+}
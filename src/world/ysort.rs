@@ -0,0 +1,229 @@
+use std::{collections::HashMap, path::Path};
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::{EntityInstance, LayerMetadata, LdtkLevel};
+use bevy_learning::ysort::YSort;
+
+/// LDtk tile layers and entity types drawn sorted with the characters instead of flat.
+///
+/// Each tile of a y-sorted layer becomes a sprite sorting on its own bottom, objects taller than a
+/// tile, like trees, sort better as entities.
+#[derive(Default)]
+pub(crate) struct LdtkYSort {
+    layers: HashMap<String, i32>,
+    entities: HashMap<String, i32>,
+}
+
+impl LdtkYSort {
+    /// Sort the tiles of the tile or auto layer `identifier` in `YSort(layer)`.
+    pub(crate) fn layer(&mut self, identifier: impl Into<String>, layer: i32) -> &mut Self {
+        self.layers.insert(identifier.into(), layer);
+        self
+    }
+
+    /// Sort the entities of type `identifier` in `YSort(layer)`.
+    pub(crate) fn entity(&mut self, identifier: impl Into<String>, layer: i32) -> &mut Self {
+        self.entities.insert(identifier.into(), layer);
+        self
+    }
+}
+
+/// Sprite spawned for a tile of a y-sorted layer.
+#[derive(Component, Default)]
+pub(crate) struct LdtkYSortTile;
+
+struct SortedTile {
+    layer: i32,
+    tileset: Handle<Image>,
+    /// Top left corner in the tileset.
+    src: Vec2,
+    size: f32,
+    /// Center, from the bottom left corner of the level.
+    translation: Vec2,
+    flip_x: bool,
+    flip_y: bool,
+    alpha: f32,
+}
+
+/// Tiles of the y-sorted layers, and the sprites spawned for them.
+#[derive(Default)]
+pub(crate) struct LdtkYSortTiles {
+    /// Tiles waiting for their tilesets, by level entity.
+    tiles: HashMap<Entity, Vec<SortedTile>>,
+    /// Atlas of each tileset, with the index of each tile by its corner.
+    atlases: HashMap<Handle<Image>, (Handle<TextureAtlas>, HashMap<(i32, i32), usize>)>,
+    /// Sprites by level entity.
+    sprites: HashMap<Entity, Vec<Entity>>,
+}
+
+/// Hide the tilemaps of the y-sorted layers, their tiles are drawn by `spawn_ysort_tiles`.
+///
+/// Only the chunks are hidden, the tiles stay, e.g. for the walls of an int grid layer.
+pub(crate) fn hide_ysort_layers(
+    config: Res<LdtkYSort>,
+    layer_query: Query<(&LayerMetadata, &Children), Changed<Children>>,
+    mut visibility_query: Query<&mut Visibility>,
+) {
+    for (layer_metadata, children) in layer_query.iter() {
+        if !config.layers.contains_key(&layer_metadata.identifier) {
+            continue;
+        }
+        for child in children.iter() {
+            if let Ok(mut visibility) = visibility_query.get_mut(*child) {
+                if visibility.is_visible {
+                    visibility.is_visible = false;
+                }
+            }
+        }
+    }
+}
+
+/// Read the tiles of the y-sorted layers of the spawned levels, and start loading their tilesets.
+pub(crate) fn collect_ysort_tiles(
+    asset_server: Res<AssetServer>,
+    levels: Res<Assets<LdtkLevel>>,
+    config: Res<LdtkYSort>,
+    mut sorted_tiles: ResMut<LdtkYSortTiles>,
+    level_query: Query<(Entity, &Handle<LdtkLevel>)>,
+) {
+    let LdtkYSortTiles { tiles, sprites, .. } = &mut *sorted_tiles;
+    for (level, handle) in level_query.iter() {
+        if tiles.contains_key(&level) || sprites.contains_key(&level) {
+            continue;
+        }
+        let ldtk_level = match levels.get(handle) {
+            Some(ldtk_level) => ldtk_level,
+            None => continue,
+        };
+        // Tilesets are relative to the project file.
+        let directory = asset_server
+            .get_handle_path(handle)
+            .and_then(|path| path.path().parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+
+        let mut level_tiles = Vec::new();
+        for layer_instance in ldtk_level.level.layer_instances.iter().flatten() {
+            let layer = match config.layers.get(&layer_instance.identifier) {
+                Some(layer) => *layer,
+                None => continue,
+            };
+            let tileset: Handle<Image> = match &layer_instance.tileset_rel_path {
+                Some(tileset) => asset_server.load(directory.join(tileset)),
+                None => continue,
+            };
+            let size = layer_instance.grid_size as f32;
+            let height = (layer_instance.c_hei * layer_instance.grid_size) as f32;
+            let offset = Vec2::new(
+                layer_instance.px_total_offset_x as f32,
+                layer_instance.px_total_offset_y as f32,
+            );
+            let layer_tiles = layer_instance
+                .grid_tiles
+                .iter()
+                .chain(layer_instance.auto_layer_tiles.iter());
+            for tile in layer_tiles {
+                let corner = Vec2::new(tile.px[0] as f32, tile.px[1] as f32) + offset;
+                level_tiles.push(SortedTile {
+                    layer,
+                    tileset: tileset.clone(),
+                    src: Vec2::new(tile.src[0] as f32, tile.src[1] as f32),
+                    size,
+                    // LDtk goes down from the top left corner.
+                    translation: Vec2::new(corner.x + size / 2., height - corner.y - size / 2.),
+                    flip_x: tile.f & 1 != 0,
+                    flip_y: tile.f & 2 != 0,
+                    alpha: layer_instance.opacity,
+                });
+            }
+        }
+        tiles.insert(level, level_tiles);
+    }
+}
+
+/// Spawn the collected tiles as y-sorted sprites of their level, once their tilesets are loaded.
+pub(crate) fn spawn_ysort_tiles(
+    mut commands: Commands,
+    images: Res<Assets<Image>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut sorted_tiles: ResMut<LdtkYSortTiles>,
+    level_query: Query<(), With<Handle<LdtkLevel>>>,
+    tile_query: Query<(), With<LdtkYSortTile>>,
+) {
+    let LdtkYSortTiles {
+        tiles,
+        atlases,
+        sprites,
+    } = &mut *sorted_tiles;
+    // Despawned with their level, e.g. when it is respawned, they are collected again.
+    sprites.retain(|level, level_sprites| {
+        level_query.get(*level).is_ok()
+            && level_sprites
+                .first()
+                .map_or(true, |sprite| tile_query.get(*sprite).is_ok())
+    });
+    tiles.retain(|level, _| level_query.get(*level).is_ok());
+
+    let loaded: Vec<Entity> = tiles
+        .iter()
+        .filter(|(_, level_tiles)| {
+            level_tiles.iter().all(|tile| {
+                atlases.contains_key(&tile.tileset) || images.get(&tile.tileset).is_some()
+            })
+        })
+        .map(|(level, _)| *level)
+        .collect();
+    for level in loaded {
+        let level_tiles = tiles.remove(&level).unwrap_or_default();
+        let mut level_sprites = Vec::with_capacity(level_tiles.len());
+        for tile in level_tiles {
+            let (atlas, indices) = atlases.entry(tile.tileset.clone()).or_insert_with(|| {
+                // Checked above.
+                let size = images.get(&tile.tileset).map_or(Vec2::ZERO, Image::size);
+                let atlas = TextureAtlas::new_empty(tile.tileset.clone(), size);
+                (texture_atlases.add(atlas), HashMap::new())
+            });
+            let index = *indices
+                .entry((tile.src.x as i32, tile.src.y as i32))
+                .or_insert_with(|| {
+                    texture_atlases.get_mut(&*atlas).map_or(0, |texture_atlas| {
+                        texture_atlas.add_texture(bevy::sprite::Rect {
+                            min: tile.src,
+                            max: tile.src + Vec2::splat(tile.size),
+                        })
+                    })
+                });
+
+            let sprite = commands
+                .spawn_bundle(SpriteSheetBundle {
+                    sprite: TextureAtlasSprite {
+                        index,
+                        flip_x: tile.flip_x,
+                        flip_y: tile.flip_y,
+                        color: Color::rgba(1., 1., 1., tile.alpha),
+                        ..default()
+                    },
+                    texture_atlas: atlas.clone(),
+                    transform: Transform::from_translation(tile.translation.extend(0.)),
+                    ..default()
+                })
+                .insert_bundle((LdtkYSortTile, YSort(tile.layer)))
+                .id();
+            level_sprites.push(sprite);
+        }
+        commands.entity(level).push_children(&level_sprites);
+        sprites.insert(level, level_sprites);
+    }
+}
+
+/// Sort the entities of the y-sorted types.
+pub(crate) fn ysort_entities(
+    mut commands: Commands,
+    config: Res<LdtkYSort>,
+    query: Query<(Entity, &EntityInstance), Added<EntityInstance>>,
+) {
+    for (entity, entity_instance) in query.iter() {
+        if let Some(layer) = config.entities.get(&entity_instance.identifier) {
+            commands.entity(entity).insert(YSort(*layer));
+        }
+    }
+}
@@ -1,37 +1,381 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, transform::TransformSystem};
 use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::ops::Range;
+
+#[cfg(test)]
+mod tests;
 
 #[derive(Component, Default, Reflect)]
 #[reflect(Component)]
 pub struct YSort(pub i32);
 
-fn sort(mut query: Query<(&mut Transform, &YSort)>) {
-    let mut keys: Vec<i32> = Vec::new();
-    let mut transformers: HashMap<i32, Vec<Mut<Transform>>> = HashMap::new();
-    for (transform, ysort) in query.iter_mut() {
-        if !transformers.contains_key(&ysort.0) {
-            transformers.insert(ysort.0, Vec::new());
-            keys.push(ysort.0);
+/// Height the entity sorts at, from its origin, e.g. `-8.` for feet 8 pixels below the center.
+///
+/// Entities without a pivot get the bottom of their sprite once it is loaded.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct YSortPivot(pub f32);
+
+fn atlas_pivot(
+    mut commands: Commands,
+    atlases: Res<Assets<TextureAtlas>>,
+    query: Query<
+        (Entity, &TextureAtlasSprite, &Handle<TextureAtlas>),
+        (With<YSort>, Without<YSortPivot>),
+    >,
+) {
+    for (entity, sprite, atlas) in query.iter() {
+        let size = sprite.custom_size.or_else(|| {
+            let rect = atlases.get(atlas)?.textures.get(sprite.index)?;
+            Some(rect.max - rect.min)
+        });
+        if let Some(size) = size {
+            commands.entity(entity).insert(YSortPivot(-size.y / 2.));
         }
-        let collector = transformers.get_mut(&ysort.0);
-        if let Some(collector) = collector {
-            collector.push(transform);
+    }
+}
+
+fn sprite_pivot(
+    mut commands: Commands,
+    images: Res<Assets<Image>>,
+    query: Query<(Entity, &Sprite, &Handle<Image>), (With<YSort>, Without<YSortPivot>)>,
+) {
+    for (entity, sprite, image) in query.iter() {
+        let size = sprite
+            .custom_size
+            .or_else(|| images.get(image).map(|image| image.size()));
+        if let Some(size) = size {
+            commands.entity(entity).insert(YSortPivot(-size.y / 2.));
         }
     }
+}
 
-    keys.sort_unstable();
-    let mut index: f32 = 0.;
-    for k in keys.iter() {
-        let v = transformers.get_mut(k).unwrap();
-        v.sort_unstable_by(|a, b| match a.translation.y.partial_cmp(&b.translation.y) {
-            None => Ordering::Equal,
-            Some(other) => other,
-        });
+/// Z range of each `YSort` layer, the entities of a layer are spread inside its band.
+///
+/// Layers without a band use `layer..layer + 1`, e.g. `YSort(4)` sorts between `4.` and `5.`,
+/// strictly inside so they never tie with a tile layer placed on a bound.
+#[derive(Default)]
+pub struct YSortBands {
+    bands: HashMap<i32, Range<f32>>,
+}
+
+impl YSortBands {
+    pub fn insert(&mut self, layer: i32, band: Range<f32>) -> &mut Self {
+        self.bands.insert(layer, band);
+        self
+    }
+
+    pub fn get(&self, layer: i32) -> Range<f32> {
+        self.bands
+            .get(&layer)
+            .cloned()
+            .unwrap_or(layer as f32..layer as f32 + 1.)
+    }
+}
+
+/// Transform the sort key is read from and `z` is sorted in.
+///
+/// `Global` sorts entities of different parents together, e.g. entities under LDtk levels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum YSortSpace {
+    #[default]
+    Local,
+    Global,
+}
+
+/// Sub-order of an entity in the slot of its closest `YSort` ancestor, from `-1.` behind it to
+/// `1.` in front of it, e.g. `-0.5` for a shadow and `0.5` for a weapon.
+///
+/// Other descendants of a sorted entity follow its new `z` one frame late.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct YSortChild(pub f32);
+
+struct YSortEntry {
+    key: f32,
+    entity: Entity,
+    /// `z` last written, `NaN` until then.
+    z: f32,
+}
+
+#[derive(Default)]
+struct YSortLayer {
+    /// Back to front, kept between frames so a layer where few entities move is almost sorted.
+    entries: Vec<YSortEntry>,
+    /// Smallest distance between the `z` of two entities, the room of their `YSortChild`.
+    step: f32,
+    dirty: bool,
+}
+
+/// Layer, sort key and last written `z` of every sorted entity, with the sorted layers.
+#[derive(Default)]
+struct YSortIndex {
+    keys: HashMap<Entity, (i32, f32, f32)>,
+    layers: HashMap<i32, YSortLayer>,
+}
+
+/// Whether `z` is off from `expected` by more than the propagation rounds it, `NaN` always is.
+fn drifted(z: f32, expected: f32, step: f32) -> bool {
+    !((z - expected).abs() <= step / 1024.)
+}
+
+/// Mark the layers whose entities moved, were added or were removed.
+fn track(
+    mut index: ResMut<YSortIndex>,
+    space: Res<YSortSpace>,
+    removed: RemovedComponents<YSort>,
+    query: Query<(&Transform, &GlobalTransform, &YSort, Option<&YSortPivot>)>,
+    changed: Query<
+        Entity,
+        (
+            With<YSort>,
+            Or<(
+                Changed<Transform>,
+                Changed<GlobalTransform>,
+                Changed<YSort>,
+                Changed<YSortPivot>,
+            )>,
+        ),
+    >,
+) {
+    let YSortIndex { keys, layers } = &mut *index;
+    for entity in removed.iter() {
+        // Removed then inserted again, it is still sorted.
+        if query.get(entity).is_ok() {
+            continue;
+        }
+        if let Some((layer, _, _)) = keys.remove(&entity) {
+            layers.entry(layer).or_default().dirty = true;
+        }
+    }
+
+    // Every key and `z` changes with the space.
+    let entities: Vec<Entity> = if space.is_changed() {
+        for sorted in layers.values_mut() {
+            sorted
+                .entries
+                .iter_mut()
+                .for_each(|entry| entry.z = f32::NAN);
+            sorted.dirty = true;
+        }
+        keys.keys().copied().chain(changed.iter()).collect()
+    } else {
+        changed.iter().collect()
+    };
+    for entity in entities {
+        let (transform, global_transform, ysort, pivot) = match query.get(entity) {
+            Ok(sorted) => sorted,
+            Err(_) => continue,
+        };
+        let (y, scale, z) = match *space {
+            YSortSpace::Local => (
+                transform.translation.y,
+                transform.scale.y,
+                transform.translation.z,
+            ),
+            YSortSpace::Global => (
+                global_transform.translation.y,
+                global_transform.scale.y,
+                global_transform.translation.z,
+            ),
+        };
+        let key = y + pivot.map_or(0., |pivot| pivot.0) * scale;
+        let written = keys
+            .get(&entity)
+            .map_or(f32::NAN, |(_, _, written)| *written);
+        match keys.insert(entity, (ysort.0, key, written)) {
+            // Only `z` changed, most likely written by `sort` itself.
+            Some((layer, previous, _)) if layer == ysort.0 && previous == key => {
+                let step = layers.get(&layer).map_or(0., |sorted| sorted.step);
+                if !drifted(z, written, step) {
+                    continue;
+                }
+            }
+            Some((layer, _, _)) if layer == ysort.0 => {}
+            previous => {
+                if let Some((layer, _, _)) = previous {
+                    layers.entry(layer).or_default().dirty = true;
+                }
+                layers.entry(ysort.0).or_default().entries.push(YSortEntry {
+                    key,
+                    entity,
+                    z: f32::NAN,
+                });
+            }
+        }
+        layers.entry(ysort.0).or_default().dirty = true;
+    }
+}
+
+/// Move `z` in `space` to `z`, keeping the local and global transforms in agreement.
+fn set_z(
+    space: YSortSpace,
+    transform: &mut Transform,
+    global_transform: &mut GlobalTransform,
+    z: f32,
+) {
+    let delta = match space {
+        YSortSpace::Local => z - transform.translation.z,
+        YSortSpace::Global => z - global_transform.translation.z,
+    };
+    transform.translation.z += delta;
+    global_transform.translation.z += delta;
+}
+
+/// `z` of the sorted `entries` inside `band` and the smallest distance between two of them.
+///
+/// Entries still in order keep their `z`, the others are spread in the gaps they fall in, so adding
+/// or moving an entity does not move its neighbours. Once gaps get too thin for `f32` the whole
+/// layer is spread again.
+fn slots(entries: &[YSortEntry], band: &Range<f32>) -> (Vec<f32>, f32) {
+    let width = band.end - band.start;
+    // Keep the entries between the previous kept one and their next one, `NaN` is never kept.
+    let mut kept = vec![false; entries.len()];
+    let mut low = band.start;
+    for (index, entry) in entries.iter().enumerate() {
+        let high = entries
+            .get(index + 1)
+            .map(|next| next.z)
+            .filter(|next| *next > entry.z)
+            .map_or(band.end, |next| next.min(band.end));
+        if entry.z > low && entry.z < high {
+            kept[index] = true;
+            low = entry.z;
+        }
+    }
+
+    let mut zs = Vec::with_capacity(entries.len());
+    let mut low = band.start;
+    let mut start = 0;
+    for index in 0..=entries.len() {
+        if index < entries.len() && !kept[index] {
+            continue;
+        }
+        let high = entries.get(index).map_or(band.end, |entry| entry.z);
+        let step = (high - low) / (index - start + 1) as f32;
+        zs.extend((start..index).map(|gap| low + step * (gap - start + 1) as f32));
+        if index < entries.len() {
+            zs.push(high);
+        }
+        low = high;
+        start = index + 1;
+    }
+
+    let min_step = zs
+        .iter()
+        .scan(band.start, |previous, z| {
+            let step = z - *previous;
+            *previous = *z;
+            Some(step)
+        })
+        .fold(width, f32::min);
+    if min_step > width / (1 << 20) as f32 {
+        return (zs, min_step);
+    }
+    let step = width / (entries.len() + 1) as f32;
+    let zs = (0..entries.len())
+        .map(|index| band.start + step * (index + 1) as f32)
+        .collect();
+    (zs, step)
+}
+
+/// Sort the marked layers, higher entities behind, and write the `z` that changed.
+fn sort(
+    mut index: ResMut<YSortIndex>,
+    bands: Res<YSortBands>,
+    space: Res<YSortSpace>,
+    mut query: Query<(&mut Transform, &mut GlobalTransform), With<YSort>>,
+) {
+    let YSortIndex { keys, layers } = &mut *index;
+    if bands.is_changed() {
+        for sorted in layers.values_mut() {
+            sorted
+                .entries
+                .iter_mut()
+                .for_each(|entry| entry.z = f32::NAN);
+            sorted.dirty = true;
+        }
+    }
+
+    for (layer, sorted) in layers.iter_mut().filter(|(_, sorted)| sorted.dirty) {
+        sorted.dirty = false;
+        sorted
+            .entries
+            .retain_mut(|entry| match keys.get(&entry.entity) {
+                Some((entry_layer, key, _)) if entry_layer == layer => {
+                    entry.key = *key;
+                    true
+                }
+                _ => false,
+            });
+        // Stable and adaptive, about linear on a layer that was sorted last frame.
+        sorted
+            .entries
+            .sort_by(|a, b| match b.key.partial_cmp(&a.key) {
+                None => Ordering::Equal,
+                Some(other) => other,
+            });
+
+        let (zs, step) = slots(&sorted.entries, &bands.get(*layer));
+        sorted.step = step;
+        for (entry, z) in sorted.entries.iter_mut().zip(zs) {
+            entry.z = z;
+            if let Some((_, _, written)) = keys.get_mut(&entry.entity) {
+                *written = z;
+            }
+            let (mut transform, mut global_transform) = match query.get_mut(entry.entity) {
+                Ok(transforms) => transforms,
+                Err(_) => continue,
+            };
+            // Also corrects a `z` written by another system.
+            let current = match *space {
+                YSortSpace::Local => transform.translation.z,
+                YSortSpace::Global => global_transform.translation.z,
+            };
+            if drifted(current, z, step) {
+                set_z(*space, &mut transform, &mut global_transform, z);
+            }
+        }
+    }
+    layers.retain(|_, sorted| !sorted.entries.is_empty());
+}
+
+/// Place `YSortChild` entities in the slot of their closest sorted ancestor.
+fn sort_children(
+    index: Res<YSortIndex>,
+    parents: Query<&Parent>,
+    sorted: Query<&GlobalTransform, (With<YSort>, Without<YSortChild>)>,
+    mut children: Query<
+        (Entity, &YSortChild, &mut Transform, &mut GlobalTransform),
+        Without<YSort>,
+    >,
+) {
+    for (entity, child, mut transform, mut global_transform) in children.iter_mut() {
+        let mut ancestor = entity;
+        let ancestor_transform = loop {
+            ancestor = match parents.get(ancestor) {
+                Ok(parent) => parent.0,
+                Err(_) => break None,
+            };
+            if let Ok(ancestor_transform) = sorted.get(ancestor) {
+                break Some(ancestor_transform);
+            }
+        };
+        let step = index
+            .keys
+            .get(&ancestor)
+            .and_then(|(layer, _, _)| index.layers.get(layer))
+            .map(|sorted| sorted.step);
+        let (ancestor_transform, step) = match (ancestor_transform, step) {
+            (Some(ancestor_transform), Some(step)) => (ancestor_transform, step),
+            _ => continue,
+        };
 
-        for transform in v.iter_mut() {
-            transform.translation.z = *k as f32 + index;
-            index += 1.;
+        let z = ancestor_transform.translation.z + child.0.clamp(-1., 1.) * step / 2.;
+        // Propagation rounds the global `z` back, only move by more than that.
+        if (z - global_transform.translation.z).abs() > step / 1024. {
+            set_z(YSortSpace::Global, &mut transform, &mut global_transform, z);
         }
     }
 }
@@ -40,8 +384,27 @@ pub struct YSortPlugin;
 
 impl Plugin for YSortPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_to_stage(CoreStage::PostUpdate, sort)
-            .register_type::<YSort>();
+        app.add_system_to_stage(CoreStage::PostUpdate, atlas_pivot)
+            .add_system_to_stage(CoreStage::PostUpdate, sprite_pivot)
+            .init_resource::<YSortBands>()
+            .init_resource::<YSortSpace>()
+            .init_resource::<YSortIndex>()
+            // Global transforms are only up to date after the propagation, `sort` updates the
+            // `z` of both transforms itself.
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                track
+                    .label("ysort_track")
+                    .after(TransformSystem::TransformPropagate),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                sort.label("ysort").after("ysort_track"),
+            )
+            .add_system_to_stage(CoreStage::PostUpdate, sort_children.after("ysort"))
+            .register_type::<YSort>()
+            .register_type::<YSortPivot>()
+            .register_type::<YSortChild>();
     }
 
     fn name(&self) -> &str {
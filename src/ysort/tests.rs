@@ -0,0 +1,164 @@
+use super::*;
+
+fn entries(zs: &[f32]) -> Vec<YSortEntry> {
+    zs.iter()
+        .enumerate()
+        .map(|(index, z)| YSortEntry {
+            key: 0.,
+            entity: Entity::from_raw(index as u32),
+            z: *z,
+        })
+        .collect()
+}
+
+#[test]
+fn new_layer_is_spread_evenly() {
+    let (zs, step) = slots(&entries(&[f32::NAN; 3]), &(0. ..1.));
+
+    assert_eq!(zs, [0.25, 0.5, 0.75]);
+    assert_eq!(step, 0.25);
+}
+
+#[test]
+fn added_entity_takes_the_gap_of_its_neighbours() {
+    let (zs, step) = slots(&entries(&[0.25, f32::NAN, 0.5, 0.75]), &(0. ..1.));
+
+    assert_eq!(zs, [0.25, 0.375, 0.5, 0.75]);
+    assert_eq!(step, 0.125);
+}
+
+#[test]
+fn removed_entity_leaves_its_neighbours() {
+    let (zs, _) = slots(&entries(&[0.25, 0.75]), &(0. ..1.));
+
+    assert_eq!(zs, [0.25, 0.75]);
+}
+
+#[test]
+fn moved_entity_is_the_only_one_to_change() {
+    // The last entity moved in front of the third one.
+    let before = [0.2, 0.4, 0.8, 0.6];
+    let (zs, _) = slots(&entries(&before), &(0. ..1.));
+
+    assert!(zs.windows(2).all(|pair| pair[0] < pair[1]));
+    let changed = zs
+        .iter()
+        .zip(before)
+        .filter(|(z, previous)| **z != *previous);
+    assert_eq!(changed.count(), 1);
+}
+
+#[test]
+fn crowded_layer_is_spread_again() {
+    let (zs, step) = slots(&entries(&[0.5, 0.5 + 1e-7, f32::NAN]), &(0. ..1.));
+
+    assert_eq!(zs, [0.25, 0.5, 0.75]);
+    assert_eq!(step, 0.25);
+}
+
+/// Entities whose `Transform` changed since the last update.
+#[derive(Default)]
+struct ChangedTransforms(Vec<Entity>);
+
+fn changed_transforms(
+    mut changed: ResMut<ChangedTransforms>,
+    query: Query<Entity, (With<YSort>, Changed<Transform>)>,
+) {
+    changed.0 = query.iter().collect();
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(bevy::asset::AssetPlugin)
+        .add_plugin(bevy::transform::TransformPlugin)
+        .add_asset::<Image>()
+        .add_asset::<TextureAtlas>()
+        .add_plugin(YSortPlugin)
+        .init_resource::<ChangedTransforms>()
+        .add_system_to_stage(CoreStage::Last, changed_transforms);
+    app
+}
+
+fn spawn(app: &mut App, layer: i32, y: f32) -> Entity {
+    app.world
+        .spawn()
+        .insert_bundle(TransformBundle::from(Transform::from_xyz(0., y, 0.)))
+        .insert(YSort(layer))
+        .id()
+}
+
+fn z(app: &App, entity: Entity) -> f32 {
+    app.world.get::<Transform>(entity).unwrap().translation.z
+}
+
+fn set_translation(app: &mut App, entity: Entity, y: f32, z: f32) {
+    let mut transform = app.world.get_mut::<Transform>(entity).unwrap();
+    transform.translation.y = y;
+    transform.translation.z = z;
+}
+
+#[test]
+fn lower_entity_is_drawn_in_front() {
+    let mut app = app();
+    let lower = spawn(&mut app, 0, 0.);
+    let higher = spawn(&mut app, 0, 10.);
+
+    app.update();
+
+    assert!(z(&app, lower) > z(&app, higher));
+}
+
+#[test]
+fn still_entities_keep_their_transform() {
+    let mut app = app();
+    let bottom = spawn(&mut app, 0, 0.);
+    let middle = spawn(&mut app, 0, 10.);
+    let top = spawn(&mut app, 0, 20.);
+    app.update();
+    app.update();
+    assert!(app.world.resource::<ChangedTransforms>().0.is_empty());
+
+    // Between the two others.
+    let top_z = z(&app, top);
+    set_translation(&mut app, top, 5., top_z);
+    app.update();
+
+    assert_eq!(app.world.resource::<ChangedTransforms>().0, [top]);
+    assert!(z(&app, bottom) > z(&app, top));
+    assert!(z(&app, top) > z(&app, middle));
+}
+
+#[test]
+fn only_moved_layers_are_sorted() {
+    let mut app = app();
+    let moving = spawn(&mut app, 0, 0.);
+    spawn(&mut app, 1, 0.);
+    app.update();
+
+    // Set again by the layers that are sorted.
+    for sorted in app.world.resource_mut::<YSortIndex>().layers.values_mut() {
+        sorted.step = -1.;
+    }
+    let moving_z = z(&app, moving);
+    set_translation(&mut app, moving, 10., moving_z);
+    app.update();
+
+    let index = app.world.resource::<YSortIndex>();
+    assert_ne!(index.layers[&0].step, -1.);
+    assert_eq!(index.layers[&1].step, -1.);
+}
+
+#[test]
+fn z_written_by_another_system_is_corrected() {
+    let mut app = app();
+    let entity = spawn(&mut app, 0, 0.);
+    spawn(&mut app, 0, 10.);
+    app.update();
+    let sorted_z = z(&app, entity);
+
+    set_translation(&mut app, entity, 0., 100.);
+    app.update();
+
+    assert_eq!(z(&app, entity), sorted_z);
+}